@@ -0,0 +1,550 @@
+// subset of the i3 ipc protocol, mapped onto tags, columns and rings
+// https://i3wm.org/docs/ipc.html
+
+use penrose::{
+    core::State,
+    x::{ XConn, XConnExt, XEvent, Prop, ClientMessage, ClientMessageData, ClientEventMask },
+    x11rb::RustConn,
+    Xid,
+    Result,
+};
+
+use serde_json::{ json, Value };
+
+use std::collections::HashMap;
+use std::io::{ Read, Write };
+use std::os::unix::net::{ UnixListener, UnixStream };
+use std::sync::{ Arc, Mutex, mpsc::{ self, Sender, SyncSender, Receiver } };
+use std::time::Duration;
+
+use crate::{ Rings, focus_column, window_class, store_view, restore_view, enter_activity, launch_template };
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const WAKE_ATOM: &str = "_RINGWM_IPC_WAKE";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+const GET_VERSION: u32 = 7;
+
+const EVENT_WORKSPACE: u32 = 0x8000_0000;
+const EVENT_WINDOW: u32 = 0x8000_0003;
+
+// larger requests are rejected instead of allocated
+const MAX_PAYLOAD: usize = 1 << 20;
+// events queued for a subscriber that isn't reading, it is dropped when this fills up
+const EVENT_QUEUE: usize = 64;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WinInfo {
+    id: Xid,
+    class: String,
+    title: String,
+    // the space of its ring, hidden members share it like i3 tabs do
+    rect: (u32, u32, u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WsInfo {
+    num: usize,
+    name: String,
+    visible: bool,
+    focused: bool,
+    output: String,
+    rect: (u32, u32, u32, u32),
+    // one ring per column, visible window first
+    cols: Vec<Vec<WinInfo>>,
+    stacked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputInfo {
+    name: String,
+    rect: (u32, u32, u32, u32),
+    // the tag on it
+    workspace: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Snapshot {
+    workspaces: Vec<WsInfo>,
+    outputs: Vec<OutputInfo>,
+    focused: Option<Xid>,
+}
+
+// events go through a writer thread per client, so a client that stops reading never blocks the wm
+struct Subscriber {
+    writer: SyncSender<Vec<u8>>,
+    workspace: bool,
+    window: bool,
+}
+
+// handled on the wm thread
+enum Request {
+    Command(String, Sender<bool>),
+    Snapshot(Sender<Snapshot>),
+}
+
+pub struct I3Ipc {
+    path: String,
+    // the last snapshot taken, only kept up to date on refresh while there are subscribers
+    snapshot: Arc<Mutex<Snapshot>>,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    requests: Receiver<Request>,
+    // (class, title) per window, dropped when its properties change
+    names: HashMap<Xid, (String, String)>,
+}
+
+impl I3Ipc {
+    fn start() -> Result<Self> {
+        let dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+        let path = format!("{dir}/ringwm-ipc.{}.sock", std::process::id());
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+
+        let snapshot = Arc::new(Mutex::new(Snapshot::default()));
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let (tx, requests) = mpsc::channel();
+
+        let (snap, subs) = (snapshot.clone(), subscribers.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (snap, subs, tx) = (snap.clone(), subs.clone(), tx.clone());
+                std::thread::spawn(move || handle_client(stream, snap, subs, tx));
+            }
+        });
+
+        Ok(Self { path, snapshot, subscribers, requests, names: HashMap::new() })
+    }
+}
+
+fn read_msg(stream: &mut UnixStream) -> std::io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "bad i3-ipc magic"));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+    let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    if len > MAX_PAYLOAD {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "i3-ipc payload too large"));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok((kind, payload))
+}
+
+fn encode_msg(kind: u32, payload: &Value) -> Vec<u8> {
+    let body = payload.to_string();
+    let mut msg = Vec::with_capacity(14 + body.len());
+    msg.extend_from_slice(MAGIC);
+    msg.extend_from_slice(&(body.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&kind.to_ne_bytes());
+    msg.extend_from_slice(body.as_bytes());
+    msg
+}
+
+fn spawn_writer(stream: &UnixStream) -> std::io::Result<SyncSender<Vec<u8>>> {
+    let mut stream = stream.try_clone()?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(EVENT_QUEUE);
+    std::thread::spawn(move || {
+        for msg in rx {
+            if stream.write_all(&msg).is_err() { return; }
+        }
+    });
+    Ok(tx)
+}
+
+// the wm blocks on x events, so poke it with a client message on the root window,
+// one x connection per client, opened on first use
+#[derive(Default)]
+struct Waker {
+    conn: Option<RustConn>,
+}
+
+impl Waker {
+    fn wake(&mut self) -> Result<()> {
+        if self.conn.is_none() {
+            self.conn = Some(RustConn::new()?);
+        }
+        let conn = self.conn.as_ref().unwrap();
+        let data = ClientMessageData::from([0u32; 5]);
+        let msg = ClientMessage::new(conn.root(), ClientEventMask::SubstructureNotify, WAKE_ATOM, data);
+        let res = conn.send_client_message(msg);
+        conn.flush();
+        if res.is_err() {
+            // try a new connection next time
+            self.conn = None;
+        }
+        res
+    }
+}
+
+// the snapshot is taken on the wm thread, falls back to the last one if the wm is busy
+fn fetch_snapshot(snapshot: &Mutex<Snapshot>, requests: &Sender<Request>, waker: &mut Waker) -> Snapshot {
+    let (tx, rx) = mpsc::channel();
+    let fresh = requests.send(Request::Snapshot(tx)).is_ok() && waker.wake().is_ok();
+    let fresh = if fresh { rx.recv_timeout(Duration::from_secs(1)).ok() } else { None };
+    fresh.unwrap_or_else(|| snapshot.lock().unwrap().clone())
+}
+
+fn handle_client(
+    mut stream: UnixStream,
+    snapshot: Arc<Mutex<Snapshot>>,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    requests: Sender<Request>,
+) {
+    // once subscribed, replies go through the writer too so they don't interleave with events
+    let mut writer: Option<SyncSender<Vec<u8>>> = None;
+    let mut waker = Waker::default();
+    while let Ok((kind, payload)) = read_msg(&mut stream) {
+        let mut subscriber = None;
+        let reply = match kind {
+            RUN_COMMAND => {
+                let cmd = String::from_utf8_lossy(&payload).to_string();
+                let (tx, rx) = mpsc::channel();
+                let ok = requests.send(Request::Command(cmd, tx)).is_ok()
+                    && waker.wake().is_ok()
+                    && rx.recv_timeout(Duration::from_secs(1)).unwrap_or(false);
+                json!([{ "success": ok }])
+            },
+            GET_WORKSPACES => workspaces_json(&fetch_snapshot(&snapshot, &requests, &mut waker)),
+            GET_OUTPUTS => outputs_json(&fetch_snapshot(&snapshot, &requests, &mut waker)),
+            GET_TREE => tree_json(&fetch_snapshot(&snapshot, &requests, &mut waker)),
+            GET_VERSION => json!({
+                "major": 4, "minor": 0, "patch": 0,
+                "human_readable": format!("ringwm {}", env!("CARGO_PKG_VERSION")),
+            }),
+            SUBSCRIBE => {
+                let events: Vec<String> = serde_json::from_slice(&payload).unwrap_or_default();
+                let workspace = events.iter().any(|e| e == "workspace");
+                let window = events.iter().any(|e| e == "window");
+                if writer.is_none() {
+                    writer = spawn_writer(&stream).ok();
+                }
+                // bring the snapshot up to date, so the first events are about changes from now on
+                fetch_snapshot(&snapshot, &requests, &mut waker);
+                subscriber = writer.clone().map(|writer| Subscriber { writer, workspace, window });
+                json!({ "success": subscriber.is_some() && (workspace || window) })
+            },
+            _ => json!({ "success": false, "error": "unsupported message type" }),
+        };
+        let msg = encode_msg(kind, &reply);
+        let sent = match &writer {
+            Some(w) => w.send(msg).is_ok(),
+            None => stream.write_all(&msg).is_ok(),
+        };
+        if !sent { return; }
+        if let Some(sub) = subscriber {
+            subscribers.lock().unwrap().push(sub);
+        }
+    }
+}
+
+fn rect_json((x, y, w, h): (u32, u32, u32, u32)) -> Value {
+    json!({ "x": x, "y": y, "width": w, "height": h })
+}
+
+fn workspaces_json(snap: &Snapshot) -> Value {
+    snap.workspaces.iter().map(|ws| json!({
+        "id": ws.num,
+        "num": ws.num,
+        "name": ws.name,
+        "visible": ws.visible,
+        "focused": ws.focused,
+        "urgent": false,
+        "output": ws.output,
+        "rect": rect_json(ws.rect),
+    })).collect()
+}
+
+fn outputs_json(snap: &Snapshot) -> Value {
+    snap.outputs.iter().map(|o| json!({
+        "name": o.name,
+        "active": true,
+        "primary": o.name == "screen-0",
+        "current_workspace": o.workspace,
+        "rect": rect_json(o.rect),
+    })).collect()
+}
+
+// the fields every i3 container has, i3ipc reads rect and the node lists unchecked
+fn con_json(kind: &str, name: &str, rect: (u32, u32, u32, u32), layout: &str, nodes: Vec<Value>) -> Value {
+    json!({
+        "type": kind,
+        "name": name,
+        "rect": rect_json(rect),
+        "layout": layout,
+        "focused": false,
+        "urgent": false,
+        "nodes": nodes,
+        "floating_nodes": [],
+        "focus": [],
+        "marks": [],
+    })
+}
+
+fn window_json(win: &WinInfo, focused: Option<Xid>) -> Value {
+    let mut con = con_json("con", &win.title, win.rect, "splith", vec![]);
+    con["id"] = json!(*win.id);
+    con["window"] = json!(*win.id);
+    con["focused"] = json!(Some(win.id) == focused);
+    con["window_rect"] = rect_json(win.rect);
+    con["window_properties"] = json!({ "class": win.class, "title": win.title });
+    con
+}
+
+fn workspace_json(ws: &WsInfo, focused: Option<Xid>) -> Value {
+    let cols = ws.cols.iter().enumerate().map(|(i, ring)| {
+        let rect = ring.first().map_or((0, 0, 0, 0), |w| w.rect);
+        let nodes = ring.iter().map(|w| window_json(w, focused)).collect();
+        con_json("con", &format!("ring-{i}"), rect, "tabbed", nodes)
+    }).collect();
+    let mut con = con_json("workspace", &ws.name, ws.rect, if ws.stacked { "splitv" } else { "splith" }, cols);
+    con["id"] = json!(ws.num);
+    con["num"] = json!(ws.num);
+    con["output"] = json!(ws.output);
+    con["visible"] = json!(ws.visible);
+    con["focused"] = json!(ws.focused);
+    con
+}
+
+fn tree_json(snap: &Snapshot) -> Value {
+    let outputs = snap.outputs.iter().map(|o| {
+        let workspaces = snap.workspaces.iter()
+            .filter(|ws| ws.output == o.name)
+            .map(|ws| workspace_json(ws, snap.focused))
+            .collect();
+        con_json("output", &o.name, o.rect, "output", workspaces)
+    }).collect();
+    let (w, h) = snap.outputs.iter()
+        .fold((0, 0), |(w, h), o| (w.max(o.rect.0 + o.rect.2), h.max(o.rect.1 + o.rect.3)));
+    con_json("root", "root", (0, 0, w, h), "splith", outputs)
+}
+
+fn take_snapshot<X: XConn>(
+    state: &State<X>,
+    x: &X,
+    rings: &Rings,
+    names: &mut HashMap<Xid, (String, String)>,
+) -> Snapshot {
+    let cs = &state.client_set;
+    let focused_tag = cs.current_tag().to_string();
+    let mut outputs = Vec::new();
+    let mut on_screen = Vec::new();
+    for screen in cs.screens() {
+        let r = screen.geometry();
+        let name = format!("screen-{}", screen.index());
+        let tag = screen.workspace.tag().to_string();
+        on_screen.push((tag.clone(), name.clone(), (r.x, r.y, r.w, r.h)));
+        outputs.push(OutputInfo { name, rect: (r.x, r.y, r.w, r.h), workspace: tag });
+    }
+
+    let mut info = |id: Xid, rect| {
+        let (class, title) = names.entry(id)
+            .or_insert_with(|| (window_class(x, id), x.window_title(id).unwrap_or_default()))
+            .clone();
+        WinInfo { id, class, title, rect }
+    };
+
+    let workspaces = rings.tag_names.iter().enumerate().map(|(i, name)| {
+        let visible = on_screen.iter().find(|(t, _, _)| t == name);
+        let (output, rect) = match visible {
+            Some((_, o, r)) => (o.clone(), *r),
+            None => (String::new(), (0, 0, 0, 0)),
+        };
+        let cols = rings.visible(i).iter()
+            .filter(|ring| ring.len() > 0)
+            .map(|ring| {
+                // only the shown member has a place on screen
+                let rect = match (visible, ring.focus()) {
+                    (Some(_), Some(id)) => x.client_geometry(id).map(|r| (r.x, r.y, r.w, r.h)).unwrap_or_default(),
+                    _ => (0, 0, 0, 0),
+                };
+                ring.members().into_iter().map(|id| info(id, rect)).collect()
+            })
+            .collect();
+        WsInfo {
+            num: i + 1,
+            name: name.clone(),
+            visible: visible.is_some(),
+            focused: *name == focused_tag,
+            output,
+            rect,
+            cols,
+            stacked: rings.stacked[i],
+        }
+    }).collect();
+
+    Snapshot { workspaces, outputs, focused: cs.current_client().copied() }
+}
+
+// never blocks, subscribers that are gone or too far behind are dropped
+fn broadcast(subscribers: &Mutex<Vec<Subscriber>>, kind: u32, payload: Value) {
+    let msg = encode_msg(kind, &payload);
+    subscribers.lock().unwrap().retain(|sub| {
+        let wanted = if kind == EVENT_WORKSPACE { sub.workspace } else { sub.window };
+        !wanted || sub.writer.try_send(msg.clone()).is_ok()
+    });
+}
+
+fn find_window(snap: &Snapshot, id: Xid) -> Option<&WinInfo> {
+    snap.workspaces.iter().flat_map(|ws| ws.cols.iter().flatten()).find(|w| w.id == id)
+}
+
+fn emit_events(ipc: &I3Ipc, old: &Snapshot, new: &Snapshot) {
+    let focused_ws = |s: &Snapshot| s.workspaces.iter().find(|ws| ws.focused).cloned();
+    let (old_ws, new_ws) = (focused_ws(old), focused_ws(new));
+    if old_ws.as_ref().map(|ws| &ws.name) != new_ws.as_ref().map(|ws| &ws.name) {
+        let ws_json = |ws: &Option<WsInfo>| ws.as_ref().map(|ws| workspace_json(ws, new.focused));
+        broadcast(&ipc.subscribers, EVENT_WORKSPACE, json!({
+            "change": "focus",
+            "current": ws_json(&new_ws),
+            "old": ws_json(&old_ws),
+        }));
+    }
+
+    for ws in &new.workspaces {
+        for win in ws.cols.iter().flatten() {
+            if find_window(old, win.id).is_none() {
+                broadcast(&ipc.subscribers, EVENT_WINDOW, json!({
+                    "change": "new", "container": window_json(win, new.focused),
+                }));
+            }
+        }
+    }
+    for ws in &old.workspaces {
+        for win in ws.cols.iter().flatten() {
+            if find_window(new, win.id).is_none() {
+                broadcast(&ipc.subscribers, EVENT_WINDOW, json!({
+                    "change": "close", "container": window_json(win, None),
+                }));
+            }
+        }
+    }
+
+    if old.focused != new.focused {
+        if let Some(win) = new.focused.and_then(|id| find_window(new, id)) {
+            broadcast(&ipc.subscribers, EVENT_WINDOW, json!({
+                "change": "focus", "container": window_json(win, new.focused),
+            }));
+        }
+    }
+}
+
+// supports: focus left|right, workspace <name>, move [container|window] to workspace <name>,
+// and as extensions: view save|restore <name>, activity <name>, template <name>
+fn run_command<X: XConn + 'static>(cmd: &str, state: &mut State<X>, x: &X) -> Result<bool> {
+    let words = cmd.split_whitespace().collect::<Vec<_>>();
+    let tags = state.client_set.ordered_tags();
+    let tag = |name: &str| {
+        let name = name.trim_matches('"');
+        tags.iter().find(|t| *t == name).cloned()
+    };
+    match words.as_slice() {
+        ["focus", dir @ ("left" | "right")] => {
+            focus_column(state, *dir == "left")?;
+            Ok(true)
+        },
+        ["workspace", name] => match tag(name) {
+            Some(t) => { state.client_set.focus_tag(&t); Ok(true) },
+            None => Ok(false),
+        },
+        ["move", "container" | "window", "to", "workspace", name] => match tag(name) {
+            Some(t) => { state.client_set.move_focused_to_tag(&t); Ok(true) },
+            None => Ok(false),
+        },
+        ["view", "save", name] => {
+            store_view(state, name.trim_matches('"'))?;
+            Ok(true)
+        },
+        ["view", "restore", name] => restore_view(state, x, name.trim_matches('"')),
+        ["template", name] => launch_template(state, name.trim_matches('"')),
+        ["activity", name] => {
+            enter_activity(state, x, name.trim_matches('"'))?;
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
+
+// hooks
+
+pub fn ipc_startup<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let ipc = match I3Ipc::start() {
+        Ok(ipc) => ipc,
+        Err(e) => {
+            println!("could not start i3 ipc: {e}");
+            return Ok(());
+        },
+    };
+    x.set_prop(x.root(), "I3_SOCKET_PATH", Prop::UTF8String(vec![ipc.path.clone()]))?;
+    std::env::set_var("I3SOCK", &ipc.path);
+    state.add_extension(ipc);
+    Ok(())
+}
+
+// takes a new snapshot and tells subscribers what changed
+fn update_snapshot<X: XConn>(state: &State<X>, x: &X, ipc: &mut I3Ipc) -> Result<Snapshot> {
+    let rings = state.extension::<Rings>()?;
+    let new = take_snapshot(state, x, &rings.borrow(), &mut ipc.names);
+    let old = std::mem::replace(&mut *ipc.snapshot.lock().unwrap(), new.clone());
+    if old != new {
+        emit_events(ipc, &old, &new);
+    }
+    Ok(new)
+}
+
+pub fn ipc_refresh<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let Ok(ipc) = state.extension::<I3Ipc>() else { return Ok(()) };
+    // without subscribers snapshots are only taken when a client asks for one
+    if ipc.borrow().subscribers.lock().unwrap().is_empty() { return Ok(()) }
+    update_snapshot(state, x, &mut ipc.borrow_mut())?;
+    Ok(())
+}
+
+pub fn ipc_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    let Ok(ipc) = state.extension::<I3Ipc>() else { return Ok(true) };
+    let msg = match event {
+        XEvent::PropertyNotify(p) => {
+            if ["WM_NAME", "_NET_WM_NAME", "WM_CLASS"].contains(&p.atom.as_str()) {
+                ipc.borrow_mut().names.remove(&p.id);
+            }
+            return Ok(true);
+        },
+        XEvent::Destroy(id) => {
+            ipc.borrow_mut().names.remove(id);
+            return Ok(true);
+        },
+        XEvent::ClientMessage(msg) => msg,
+        _ => return Ok(true),
+    };
+    if msg.dtype != WAKE_ATOM { return Ok(true) }
+    let pending = ipc.borrow().requests.try_iter().collect::<Vec<_>>();
+    let mut ran = false;
+    let mut waiting = Vec::new();
+    for request in pending {
+        match request {
+            Request::Command(cmd, reply) => {
+                let ok = run_command(&cmd, state, x).unwrap_or(false);
+                let _ = reply.send(ok);
+                ran = true;
+            },
+            Request::Snapshot(reply) => waiting.push(reply),
+        }
+    }
+    if ran {
+        x.refresh(state)?;
+    }
+    if !waiting.is_empty() {
+        let snap = update_snapshot(state, x, &mut ipc.borrow_mut())?;
+        for reply in waiting {
+            let _ = reply.send(snap.clone());
+        }
+    }
+    Ok(false)
+}
@@ -1,12 +1,12 @@
 use penrose::{
     builtin::{
         actions::{
-            exit, modify_with, spawn, key_handler,
+            exit, modify_with, spawn, key_handler, send_layout_message,
         },
-        layout::transformers::{ ReserveTop, Gaps },
+        layout::messages::{ ExpandMain, ShrinkMain },
     },
     core::{
-        layout::{ Layout, LayoutStack, Message },
+        layout::{ Layout, LayoutStack, Message, IntoMessage },
         bindings::{
             parse_keybindings_with_xmodmap, KeyEventHandler,
         },
@@ -17,7 +17,8 @@ use penrose::{
         hooks::{ add_ewmh_hooks },
         util::dmenu::{ DMenu, DMenuConfig, DMenuKind, MenuMatch },
     },
-    x::{ XConn, XConnExt, XEvent, query::AppName, Atom, Prop },
+    util::spawn as spawn_cmd,
+    x::{ XConn, XConnExt, XEvent, query::{ AppName, ClassName }, Atom, Prop, ClientAttr, ClientConfig },
     pure::{ Stack, StackSet, geometry::Rect },
     Xid,
     stack,
@@ -30,9 +31,52 @@ use penrose::{
 use std::collections::{ HashMap, HashSet };
 use std::sync::Arc;
 use std::cell::RefCell;
+use std::time::{ Duration, Instant };
+use std::process::{ Command, Stdio };
 
 use tracing_subscriber::{ self, prelude::* };
 
+mod ipc;
+mod overlay;
+
+use overlay::RingOverlay;
+
+// serve a subset of the i3 ipc protocol for bars and scripts
+const I3_IPC: bool = true;
+// spawned by toggle_scratchpad when no scratchpad is linked, a new window with this
+// WM_CLASS instance name is linked automatically
+const SCRATCHPAD_CMD: &str = "st -n scratchpad";
+const SCRATCHPAD_INSTANCE: &str = "scratchpad";
+// size of the floating scratchpad, None to use the size the window originally asked for
+const SCRATCHPAD_FLOAT_SIZE: Option<(u32, u32)> = None;
+// spawned into the focused tag by name, windows are matched by _NET_WM_PID,
+// by class only for programs marked as forking and windows that don't set a pid
+const TEMPLATES: &[(&str, &[TemplateSlot])] = &[
+    ("dev", &[
+        TemplateSlot { cmd: "st -e nvim", class: "st-256color", col: 0, forks: false },
+        TemplateSlot { cmd: "st", class: "st-256color", col: 1, forks: false },
+        TemplateSlot { cmd: "st", class: "st-256color", col: 1, forks: false },
+    ]),
+    ("web", &[
+        TemplateSlot { cmd: "firefox", class: "firefox", col: 0, forks: true },
+        TemplateSlot { cmd: "st", class: "st-256color", col: 1, forks: false },
+    ]),
+];
+// windows of a template that take longer to map than this are placed like any other
+const TEMPLATE_TIMEOUT: Duration = Duration::from_secs(30);
+// list the ring members for a moment after rotating or swapping in a ring, None to disable
+const RING_OVERLAY: Option<Duration> = Some(Duration::from_millis(800));
+
+struct TemplateSlot {
+    cmd: &'static str,
+    // WM_CLASS class
+    class: &'static str,
+    // the column of the ring to go into
+    col: usize,
+    // the window may come from another process than the one spawned
+    forks: bool,
+}
+
 fn raw_key_bindings() -> HashMap<String, Box<dyn KeyEventHandler<RustConn>>> {
     let mut raw_bindings = map! {
         map_keys: |k: &str| k.to_string();
@@ -44,20 +88,43 @@ fn raw_key_bindings() -> HashMap<String, Box<dyn KeyEventHandler<RustConn>>> {
         "M-f" => modify_with(|cs| cs.kill_focused()),
         "M-S-f" => toggle_floating_focused_remember(),
         "M-n" => toggle_fullscreen(),
+        "M-S-n" => toggle_monocle(),
+        "M-s" => toggle_sticky_column(),
+        "M-p" => toggle_pin(),
+        "M-k" => toggle_column_lock(),
+        "M-i" => toggle_link(),
+        "M-S-i" => rotate_pairs(),
+        "M-v" => load_view(),
+        "M-S-v" => save_view(),
+        "M-u" => switch_activity(),
+        "M-S-u" => send_to_activity(),
+        "M-t" => template_menu(),
         "M-o" => move_focus(false),
         "M-a" => move_focus(true),
         "M-S-o" => ring_rotate(true),
         "M-S-a" => ring_rotate(false),
-        "M-e" => swap_cols(),
+        "M-e" => swap_cols(true),
+        "M-equal" => send_layout_message(|| ExpandMain),
+        "M-minus" => send_layout_message(|| ShrinkMain),
+        "M-S-equal" => send_layout_message(|| ResetSplit),
+        "M-bracketright" => send_layout_message(|| ChangeGaps(2)),
+        "M-bracketleft" => send_layout_message(|| ChangeGaps(-2)),
         "M-S-e" => action_menu(),
+        "M-S-h" => window_switcher(),
         "M-comma" => swap_ring(false),
         "M-period" => swap_ring(true),
         "M-space" => toggle_scratchpad(),
         "M-S-space" => link_scratchpad(),
+        "M-C-space" => toggle_scratchpad_floating(),
         "M-S-y" => log_status(),
         "M-S-t" => exit(),
     };
 
+    for n in 1..=9 {
+        raw_bindings.insert(format!("M-{n}"), ring_jump(RingIndex::Nth(n - 1)));
+    }
+    raw_bindings.insert("M-0".to_string(), ring_jump(RingIndex::Last));
+
     for tag in &["g", "m", "l", "w"] {
         raw_bindings.extend([
             (
@@ -77,11 +144,9 @@ fn raw_key_bindings() -> HashMap<String, Box<dyn KeyEventHandler<RustConn>>> {
 fn layouts() -> LayoutStack {
     let gap_outer = 2;
     let gap_inner = 4;
-    let bar_height = 24;
     stack!(
-        Cols::boxed()
+        Cols::boxed(gap_outer, gap_inner)
     )
-    .map(|l| ReserveTop::wrap(Gaps::wrap(l, gap_outer, gap_inner), bar_height))
 }
 
 #[derive(Debug, Default)]
@@ -89,48 +154,202 @@ struct OgWindowSize {
     pub map: HashMap<Xid, (u32, u32)>,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct Cols;
+// border width last sent to each window
+#[derive(Debug, Default)]
+struct BorderWidths {
+    pub map: HashMap<Xid, u32>,
+}
+
+// which tag was shown on which screen, for every monitor setup seen so far
+#[derive(Debug, Default)]
+struct ScreenMemory {
+    pub map: HashMap<ScreenKey, Vec<String>>,
+}
+
+// x, y, w, h of every screen, in screen index order
+type ScreenKey = Vec<(u32, u32, u32, u32)>;
+
+fn screen_key(rects: &[Rect]) -> ScreenKey {
+    rects.iter().map(|r| (r.x, r.y, r.w, r.h)).collect()
+}
+
+// space reserved by docks (bars) through _NET_WM_STRUT_PARTIAL or _NET_WM_STRUT
+#[derive(Debug, Default)]
+struct Struts {
+    // left, right, top, bottom, then the start and end of each, see the EWMH spec
+    pub map: HashMap<Xid, [u32; 12]>,
+    // reserved space last sent to the layout of each tag
+    pub applied: HashMap<String, SetReserved>,
+}
+
+fn read_strut<X: XConn>(x: &X, id: Xid) -> Option<[u32; 12]> {
+    if let Ok(Some(Prop::Cardinal(v))) = x.get_prop(id, "_NET_WM_STRUT_PARTIAL") {
+        if v.len() >= 12 {
+            let mut strut = [0; 12];
+            strut.copy_from_slice(&v[..12]);
+            return Some(strut);
+        }
+    }
+    if let Ok(Some(Prop::Cardinal(v))) = x.get_prop(id, "_NET_WM_STRUT") {
+        if v.len() >= 4 {
+            let m = u32::MAX;
+            return Some([v[0], v[1], v[2], v[3], 0, m, 0, m, 0, m, 0, m]);
+        }
+    }
+    None
+}
+
+// struts are relative to the edges of the root window, which spans all screens
+fn reserved_for(s: Rect, root: (u32, u32), struts: &HashMap<Xid, [u32; 12]>) -> SetReserved {
+    let (rw, rh) = root;
+    let overlaps = |start: u32, end: u32, from: u32, len: u32| start < from + len && end >= from;
+    let mut res = SetReserved::default();
+    for &[l, r, t, b, ly0, ly1, ry0, ry1, tx0, tx1, bx0, bx1] in struts.values() {
+        if t > s.y && overlaps(tx0, tx1, s.x, s.w) {
+            res.top = res.top.max(t - s.y);
+        }
+        if b > 0 && rh.saturating_sub(b) < s.y + s.h && overlaps(bx0, bx1, s.x, s.w) {
+            res.bottom = res.bottom.max(s.y + s.h - rh.saturating_sub(b));
+        }
+        if l > s.x && overlaps(ly0, ly1, s.y, s.h) {
+            res.left = res.left.max(l - s.x);
+        }
+        if r > 0 && rw.saturating_sub(r) < s.x + s.w && overlaps(ry0, ry1, s.y, s.h) {
+            res.right = res.right.max(s.x + s.w - rw.saturating_sub(r));
+        }
+    }
+    res
+}
+
+// space to keep free at the edges of the screen the tag is on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetReserved {
+    top: u32,
+    bottom: u32,
+    left: u32,
+    right: u32,
+}
+
+impl IntoMessage for SetReserved {}
+
+// resets the column split back to 50/50
+#[derive(Debug, Clone, Copy)]
+pub struct ResetSplit;
+
+impl IntoMessage for ResetSplit {}
+
+// place the rings top to bottom instead of side by side
+#[derive(Debug, Clone, Copy)]
+pub struct SetStacked(pub bool);
+
+impl IntoMessage for SetStacked {}
+
+// grow (or shrink when negative) both the outer and inner gaps
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeGaps(pub i32);
+
+impl IntoMessage for ChangeGaps {}
+
+const SPLIT_STEP: f32 = 0.05;
+// largest gap ChangeGaps goes to, narrow columns get smaller gaps still
+const MAX_GAP: u32 = 64;
+// a lone window wider than this (in pixels) is centered with margins, None to fill the screen
+const SINGLE_MAX_WIDTH: Option<u32> = None;
+
+// every tag gets its own clone of the layout, so the ratio and gaps are remembered per tag
+// gaps are only applied when more than one window is shown
+#[derive(Debug, Clone)]
+pub struct Cols {
+    ratio: f32,
+    stacked: bool,
+    gap_outer: u32,
+    gap_inner: u32,
+    reserved: SetReserved,
+}
 
 impl Cols {
-    pub fn boxed() -> Box<dyn Layout> { Box::new(Self) }
+    pub fn boxed(gap_outer: u32, gap_inner: u32) -> Box<dyn Layout> {
+        Box::new(Self { ratio: 0.5, stacked: false, gap_outer, gap_inner, reserved: SetReserved::default() })
+    }
 }
 
 impl Layout for Cols {
-    fn name(&self) -> String { "2col".to_string() }
+    fn name(&self) -> String { "cols".to_string() }
     fn boxed_clone(&self) -> Box<dyn Layout> { Box::new(self.clone()) }
 
     fn layout(&mut self, s: &Stack<Xid>, rect: Rect) -> (Option<Box<dyn Layout>>, Vec<(Xid, Rect)>) {
-        let mut l: Option<Xid> = None;
-        let mut r: Option<Xid> = None;
-        let mut ps = Vec::with_capacity(2);
-
-        for id in s {
-            if l.is_none() { l = Some(*id); }
-            else if r.is_none() { r = Some(*id); }
-            else { break; }
-        }
+        let res = self.reserved;
+        let rect = Rect {
+            x: rect.x + res.left,
+            y: rect.y + res.top,
+            w: rect.w.saturating_sub(res.left + res.right),
+            h: rect.h.saturating_sub(res.top + res.bottom),
+        };
+        let ids = s.iter().take(MAX_COLS).copied().collect::<Vec<_>>();
 
-        if let (Some(l), Some(r)) = (l, r) {
-            let (lr, rr) = rect.split_at_width_perc(0.5).expect("could not split rings rec");
-            ps.push((l, lr));
-            ps.push((r, rr));
-        } else if let Some(l) = l {
-            ps.push((l, rect));
-        }
+        let ps = match ids.len() {
+            0 => vec![],
+            1 => match SINGLE_MAX_WIDTH {
+                Some(max_w) if !self.stacked && rect.w > max_w => {
+                    let r = Rect { x: 0, y: 0, w: max_w, h: rect.h };
+                    vec![(ids[0], r.centered_in(&rect).unwrap_or(rect))]
+                },
+                _ => vec![(ids[0], rect)],
+            },
+            k => {
+                // a gap never takes more than half of what it is cut from
+                let gap = |r: &Rect, g: u32| g.min(r.w.min(r.h) / 4);
+                let rect = rect.shrink_in(gap(&rect, self.gap_outer));
+                // the ratio is the share of the left column when there are two
+                let perc = (self.ratio * 2.0 / k as f32).min(0.9);
+                let mut rects = Vec::with_capacity(k);
+                if self.stacked {
+                    let (tr, rest) = rect.split_at_height_perc(perc).expect("could not split rings rec");
+                    rects.push(tr);
+                    rects.extend(rest.as_rows(k as u32 - 1));
+                } else {
+                    let (lr, rest) = rect.split_at_width_perc(perc).expect("could not split rings rec");
+                    rects.push(lr);
+                    rects.extend(rest.as_columns(k as u32 - 1));
+                }
+                ids.into_iter().zip(rects.into_iter().map(|r| r.shrink_in(gap(&r, self.gap_inner)))).collect()
+            },
+        };
 
         (None, ps)
     }
 
-    fn handle_message(&mut self, _: &Message) -> Option<Box<dyn Layout>> {
+    fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
+        if m.downcast_ref::<ExpandMain>().is_some() {
+            self.ratio = (self.ratio + SPLIT_STEP).min(0.9);
+        } else if m.downcast_ref::<ShrinkMain>().is_some() {
+            self.ratio = (self.ratio - SPLIT_STEP).max(0.1);
+        } else if m.downcast_ref::<ResetSplit>().is_some() {
+            self.ratio = 0.5;
+        } else if let Some(SetStacked(stacked)) = m.downcast_ref::<SetStacked>() {
+            self.stacked = *stacked;
+        } else if let Some(res) = m.downcast_ref::<SetReserved>() {
+            self.reserved = *res;
+        } else if let Some(ChangeGaps(d)) = m.downcast_ref::<ChangeGaps>() {
+            self.gap_outer = self.gap_outer.saturating_add_signed(*d).min(MAX_GAP);
+            self.gap_inner = self.gap_inner.saturating_add_signed(*d).min(MAX_GAP);
+        }
         None
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum RingIndex {
+    Nth(usize),
+    Last,
+}
+
 #[derive(Debug, Default, Clone)]
 struct Ring {
     ring: Vec<Xid>,
     focus: usize,
+    // no rotating, swallowing or swapping columns
+    locked: bool,
 }
 
 impl Ring {
@@ -143,6 +362,13 @@ impl Ring {
         else { Some(self.ring[self.focus]) }
     }
 
+    // all members, starting at the focused one
+    fn members(&self) -> Vec<Xid> {
+        let mut res = self.ring.clone();
+        res.rotate_left(self.focus.min(res.len()));
+        res
+    }
+
     fn insert(&mut self, id: Xid) {
         if self.ring.is_empty() {
             self.ring.push(id);
@@ -168,25 +394,38 @@ impl Ring {
         }
     }
 
+    // insert right after the focused member without showing it
+    fn park(&mut self, id: Xid) {
+        if self.ring.is_empty() {
+            self.ring.push(id);
+        } else {
+            self.ring.insert(self.focus + 1, id);
+        }
+    }
+
+    // returns newly focused on id
+    fn jump(&mut self, index: RingIndex) -> Option<Xid> {
+        if self.ring.is_empty() { return None; }
+        self.focus = match index {
+            RingIndex::Nth(n) => n.min(self.len() - 1),
+            RingIndex::Last => self.len() - 1,
+        };
+        Some(self.ring[self.focus])
+    }
+
     // returns (is empty due to deletion, id if it needs to be switched in)
+    // removing a hidden member keeps the shown one, removing the shown one shows the previous
     fn delete(&mut self, id: Xid) -> (bool, Option<Xid>) {
-        let ol = self.ring.len();
-        self.ring.retain(|&e| e != id);
-        let nl = self.ring.len();
-        if ol == nl { return (false, None); }
-        let mut f = (self.focus as i32) - 1;
-        if f < 0 {
-            f = self.len() as i32 - 1;
-        }
-        if f < 0 {
-            f = 0;
-        }
-        self.focus = f as usize;
+        let p = match self.ring.iter().position(|&e| e == id) { Some(p) => p, None => return (false, None) };
+        self.ring.remove(p);
         if self.ring.is_empty() {
-            (true, None)
-        } else {
-            (false, Some(self.ring[self.focus]))
+            self.focus = 0;
+            return (true, None);
         }
+        if p <= self.focus {
+            self.focus = (self.focus + self.len() - 1) % self.len();
+        }
+        (false, Some(self.ring[self.focus]))
     }
 
     fn swap(&mut self, right: bool) {
@@ -211,14 +450,125 @@ impl Ring {
     }
 }
 
+// at most this many columns (and thus rings) per tag
+const MAX_COLS: usize = 4;
+// columns per screen, by screen index, unlisted screens get 2
+const SCREEN_COLUMNS: &[usize] = &[2, 2];
+
+// rings top to bottom per screen, by screen index, None (and unlisted) means stacked
+// when the screen is taller than wide
+const SCREEN_STACKED: &[Option<bool>] = &[None, None];
+
+// opacity of visible windows that do not have focus (picom does the rest), None to disable
+const INACTIVE_OPACITY: Option<f32> = Some(0.85);
+// never dimmed, by WM_CLASS class name
+const NO_DIM_CLASSES: &[&str] = &["mpv", "vlc"];
+
+// border colors for windows in a special state, the focused color wins except for the scratchpad
+const SCRATCHPAD_BORDER: u32 = 0x9ECE6AFF;
+const FLOATING_BORDER: u32 = 0xE0AF68FF;
+const PINNED_BORDER: u32 = 0xBB9AF7FF;
+// the window shown has hidden ring members, rotating reveals something
+const HIDDEN_BORDER: u32 = 0x7AA2F7FF;
+
+fn screen_columns(screen_index: usize) -> usize {
+    SCREEN_COLUMNS.get(screen_index).copied().unwrap_or(2).clamp(1, MAX_COLS)
+}
+
+fn screen_stacked(screen_index: usize, r: Rect) -> bool {
+    SCREEN_STACKED.get(screen_index).copied().flatten().unwrap_or(r.h > r.w)
+}
+
+// what a tag was showing, saved under a name
+#[derive(Debug, Clone)]
+struct View {
+    tag: usize,
+    // the focused member of each column
+    shown: Vec<Xid>,
+    focus: Option<Xid>,
+    fullscreen: Option<Xid>,
+    monocle: bool,
+}
+
+// a window a template spawned, waiting to be mapped
+#[derive(Debug, Clone)]
+struct Pending {
+    pid: u32,
+    class: String,
+    tag: usize,
+    col: usize,
+    // its window may come from another process, so it can be matched by class
+    forks: bool,
+    spawned: Instant,
+}
+
+// a full set of tag rings, the active activity keeps its rings in Rings itself
+#[derive(Debug, Default, Clone)]
+struct Activity {
+    tags: [Vec<Ring>; 4],
+    // the column counts its rings were laid out for when it was parked
+    cols: [usize; 4],
+    merged: [Vec<(usize, Ring)>; 4],
+    active_col: [usize; 4],
+    monocle: HashSet<usize>,
+}
+
+impl Activity {
+    fn new(cols: [usize; 4]) -> Self {
+        Self {
+            tags: std::array::from_fn(|_| vec![Ring::default(); MAX_COLS]),
+            cols,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 struct Rings {
-    tags: [(Ring, Ring); 4],
+    // per tag one ring per column, always MAX_COLS long, only the first cols[i] are in use
+    tags: [Vec<Ring>; 4],
+    cols: [usize; 4],
+    // per tag, whether its rings are placed top to bottom (portrait screens)
+    stacked: [bool; 4],
     tag_indices: HashMap<String, usize>,
     tag_names: [String; 4],
     last_focus: Option<Xid>,
     scratchpad: Option<Xid>,
+    // (tag index, column, position) the scratchpad had as a regular ring member before linking
+    scratchpad_origin: Option<(usize, usize, usize)>,
+    // the scratchpad floating above the columns of this tag, outside of any ring
+    scratchpad_float: Option<(String, Rect)>,
+    // focused before the floating scratchpad was summoned
+    scratchpad_return: Option<Xid>,
     fullscreen: HashSet<Xid>,
+    // tags that only show the focused column
+    monocle: HashSet<usize>,
+    // per tag, the column that had focus last
+    active_col: [usize; 4],
+    // per tag, whether the sticky column had focus last instead
+    sticky_active: [bool; 4],
+    // per tag, (column merged into, ring) for rings that lost their column to a sticky
+    // column or a screen with fewer columns
+    merged: [Vec<(usize, Ring)>; 4],
+    // per screen index: (column, ring) shown in that column on every tag the screen displays
+    sticky: HashMap<usize, (usize, Ring)>,
+    // per tag, the index of the screen it is on, None when hidden
+    screen_of: [Option<usize>; 4],
+    // windows that currently have a lowered opacity
+    dimmed: HashSet<Xid>,
+    // toggled to floating by hand
+    floating: HashSet<Xid>,
+    // never swallowed by new windows
+    pinned: HashSet<Xid>,
+    // pairs of windows in different columns of a tag that are shown together, both directions
+    links: HashMap<Xid, Xid>,
+    views: HashMap<String, View>,
+    activity: String,
+    // the rings of the other activities, their windows wait in reikai
+    activities: HashMap<String, Activity>,
+    pending: Vec<Pending>,
+    // state specific border colors, computed on rebuild
+    borders: HashMap<Xid, u32>,
 }
 
 
@@ -235,79 +585,266 @@ impl Rings {
         for (i, tag) in ["g", "m", "l", "w"].iter().enumerate() {
             rings.tag_indices.insert(tag.to_string(), i);
             rings.tag_names[i] = tag.to_string();
+            rings.tags[i] = vec![Ring::default(); MAX_COLS];
+            rings.cols[i] = 2;
         }
+        rings.activity = "default".to_string();
         rings
     }
 
+    fn visible(&self, index: usize) -> &[Ring] {
+        &self.tags[index][..self.cols[index]]
+    }
+
+    // focused ids of the columns in use, left to right, including a sticky column
+    fn focuses(&self, index: usize) -> Vec<Xid> {
+        let mut res = self.visible(index).iter().filter_map(|r| r.focus()).collect::<Vec<_>>();
+        if let Some((c, ring)) = self.sticky_for(index) {
+            if let Some(id) = ring.focus() {
+                res.insert((*c).min(res.len()), id);
+            }
+        }
+        res
+    }
+
+    fn sticky_for(&self, index: usize) -> Option<&(usize, Ring)> {
+        self.screen_of[index].and_then(|si| self.sticky.get(&si))
+    }
+
+    // a sticky column takes one of the columns of the screen
+    fn wanted_columns(&self, screen_index: usize) -> usize {
+        let n = screen_columns(screen_index);
+        if self.sticky.contains_key(&screen_index) { n - 1 } else { n }
+    }
+
+    // the ring of the tag (or the sticky one on its screen) in which id is the focused member
+    fn focused_ring_mut(&mut self, index: usize, id: Xid) -> Option<&mut Ring> {
+        if let Some(c) = self.col_of(index, id) {
+            return Some(&mut self.tags[index][c]);
+        }
+        let si = self.screen_of[index]?;
+        self.sticky.get_mut(&si).map(|(_, ring)| ring).filter(|ring| ring.focus() == Some(id))
+    }
+
+    // returns true if id is now in a sticky column
+    fn toggle_sticky(&mut self, id: Xid, ws_label: &str, screen_index: usize) -> bool {
+        let index = match self.tag_indices.get(ws_label) { Some(i) => *i, None => return false };
+        let is_sticky = self.sticky.get(&screen_index).is_some_and(|(_, r)| r.focus() == Some(id));
+        if is_sticky {
+            let (c, ring) = self.sticky.remove(&screen_index).unwrap();
+            self.insert_col(index, c, ring);
+            return false;
+        }
+        if self.sticky.contains_key(&screen_index) || screen_columns(screen_index) < 2 {
+            return false;
+        }
+        match self.col_of(index, id) {
+            Some(c) => {
+                let ring = self.remove_col(index, c);
+                self.sticky.insert(screen_index, (c, ring));
+                true
+            },
+            None => false,
+        }
+    }
+
+    // takes column c out of the tag, the columns to its right shift left
+    fn remove_col(&mut self, index: usize, c: usize) -> Ring {
+        let ring = self.tags[index].remove(c);
+        self.tags[index].push(Ring::default());
+        merged_col_removed(&mut self.merged[index], c);
+        if self.active_col[index] > c {
+            self.active_col[index] -= 1;
+        }
+        ring
+    }
+
+    // puts ring in as column c, the columns from c on shift right and the tag uses a column more,
+    // a tag that uses all of them merges its last one first
+    fn insert_col(&mut self, index: usize, c: usize, ring: Ring) {
+        if self.cols[index] == MAX_COLS {
+            self.set_columns(index, MAX_COLS - 1);
+        }
+        let c = c.min(self.cols[index]);
+        let rings = &mut self.tags[index];
+        rings.insert(c, ring);
+        // only an unused, empty ring falls off
+        rings.truncate(MAX_COLS);
+        for (host, _) in self.merged[index].iter_mut() {
+            if *host >= c { *host += 1; }
+        }
+        if self.active_col[index] >= c {
+            self.active_col[index] += 1;
+        }
+        self.cols[index] += 1;
+    }
+
+    fn col_of(&self, index: usize, id: Xid) -> Option<usize> {
+        self.visible(index).iter().position(|r| r.focus() == Some(id))
+    }
+
+    // shrinking merges the rings that fall off into the last remaining column, growing splits
+    // them back out with the members that are still in that column
+    fn set_columns(&mut self, index: usize, n: usize) {
+        let n = n.clamp(1, MAX_COLS);
+        let old = self.cols[index];
+        if n < old {
+            let rings = &mut self.tags[index];
+            for c in n..old {
+                let ring = std::mem::take(&mut rings[c]);
+                rings[n - 1].ring.extend(ring.members());
+                if ring.len() > 0 {
+                    self.merged[index].push((n - 1, ring));
+                }
+            }
+            self.active_col[index] = self.active_col[index].min(n - 1);
+        }
+        if n > old {
+            for c in old..n {
+                // a column in use is never overwritten
+                if self.merged[index].is_empty() || self.tags[index][c].len() > 0 { break; }
+                let (host, mut ring) = self.merged[index].remove(0);
+                let host_ring = &mut self.tags[index][host];
+                let (shown, was_shown) = (host_ring.focus(), ring.focus());
+                ring.ring.retain(|id| host_ring.ring.contains(id) && Some(*id) != shown);
+                host_ring.ring.retain(|id| !ring.ring.contains(id));
+                host_ring.focus = shown.and_then(|f| host_ring.ring.iter().position(|e| *e == f)).unwrap_or(0);
+                ring.focus = was_shown.and_then(|f| ring.ring.iter().position(|e| *e == f)).unwrap_or(0);
+                self.tags[index][c] = ring;
+            }
+            // columns close up, also when a split back ring came out empty
+            let rings = &mut self.tags[index];
+            rings.retain(|r| r.len() > 0);
+            rings.resize(MAX_COLS, Ring::default());
+        }
+        self.cols[index] = n;
+    }
+
     fn current_view(&self) -> Vec<(String, Vec<Xid>)> {
         let mut res = Vec::new();
         for (i, tname) in self.tag_names.iter().enumerate() {
-            let mut tag_windows = Vec::new();
-            let (l, r) = &self.tags[i];
-            let (l, r) = (l.focus(), r.focus());
-            if let Some(l) = l { tag_windows.push(l); }
-            if let Some(r) = r { tag_windows.push(r); }
+            let mut tag_windows = self.focuses(i);
+            if self.monocle.contains(&i) {
+                let sticky = self.sticky_for(i).filter(|_| self.sticky_active[i]).and_then(|(_, r)| r.focus());
+                let active = sticky.or_else(|| self.visible(i).get(self.active_col[i]).and_then(|r| r.focus()));
+                let active = active.or(tag_windows.first().copied());
+                tag_windows = active.into_iter().collect();
+            }
             res.push((tname.clone(), tag_windows));
         }
         res
     }
 
-    fn is_focused_in_a_ring(&self, id: Xid) -> bool {
-        for (i, _) in self.tag_names.iter().enumerate() {
-            let (l, r) = &self.tags[i];
-            if l.focus() == Some(id) || r.focus() == Some(id) {
+    // remember focus, and which column of its tag it is in
+    fn set_focus(&mut self, id: Xid) {
+        self.last_focus = Some(id);
+        for i in 0..self.tags.len() {
+            if let Some(c) = self.col_of(i, id) {
+                self.active_col[i] = c;
+                self.sticky_active[i] = false;
+            } else if self.sticky_for(i).is_some_and(|(_, r)| r.focus() == Some(id)) {
+                self.sticky_active[i] = true;
+            }
+        }
+    }
+
+    // returns true if the tag is now in monocle mode
+    fn toggle_monocle(&mut self, ws_label: &str) -> bool {
+        if let Some(index) = self.tag_indices.get(ws_label) {
+            if !self.monocle.remove(index) {
+                self.monocle.insert(*index);
                 return true;
             }
         }
         false
     }
 
-    fn get_left_or_right_or_only(&self, left: bool, ws_label: &str) -> Option<Xid> {
-        if let Some(index) = self.tag_indices.get(ws_label) {
-            let (l, r) = &self.tags[*index];
-            return match (l.focus(), r.focus(), left) {
-                (Some(xid), _, true) => Some(xid),
-                (None, Some(xid), true) => Some(xid),
-                (_, Some(xid), false) => Some(xid),
-                (Some(xid), None, false) => Some(xid),
-                _ => None,
+    // (tag index, column, position in ring) of any ring member
+    fn locate(&self, id: Xid) -> Option<(usize, usize, usize)> {
+        for (i, cols) in self.tags.iter().enumerate() {
+            for (c, ring) in cols.iter().enumerate() {
+                if let Some(p) = ring.ring.iter().position(|&e| e == id) {
+                    return Some((i, c, p));
+                }
             }
         }
         None
     }
 
-    fn move_focus<'a>(&self, move_left: bool, fid: Xid, ws_labels: &'a[String]) -> FocusMove<'a> {
-        let mut col_ring = Vec::new();
-        for ws_label in ws_labels {
-            if let Some(index) = self.tag_indices.get(ws_label) {
-                let (l, r) = &self.tags[*index];
-                let (l, r) = (l.focus(), r.focus());
-                match (l, r) {
-                    (Some(lid), Some(rid)) => {
-                        if !self.fullscreen.contains(&rid) {
-                            col_ring.push(FocusMove::Id(lid));
-                        }
-                        if !self.fullscreen.contains(&lid) {
-                            col_ring.push(FocusMove::Id(rid));
-                        }
-                    },
-                    (Some(lid), _) => col_ring.push(FocusMove::Id(lid)),
-                    (_, Some(rid)) => col_ring.push(FocusMove::Id(rid)),
-                    (_, _) => col_ring.push(FocusMove::Tag(ws_label)),
+    // the column in use to put a window into for column c,
+    // columns close up when emptied, so don't leave a gap
+    fn free_col(&self, index: usize, c: usize) -> usize {
+        let c = c.min(self.cols[index] - 1);
+        self.tags[index][..c].iter().position(|r| r.len() == 0).unwrap_or(c)
+    }
+
+    // put id back at a place found by locate, keeping the shown window the same
+    fn insert_at(&mut self, id: Xid, (i, c, p): (usize, usize, usize)) {
+        let c = self.free_col(i, c);
+        let ring = &mut self.tags[i][c];
+        let p = p.min(ring.len());
+        if ring.len() > 0 && p <= ring.focus {
+            ring.focus += 1;
+        }
+        ring.ring.insert(p, id);
+    }
+
+    // the ring in which id is the focused member
+    fn ring_of(&self, id: Xid) -> Option<&Ring> {
+        (0..self.tags.len()).find_map(|i| self.col_of(i, id).map(|c| &self.tags[i][c]))
+            .or_else(|| self.sticky.values().map(|(_, r)| r).find(|r| r.focus() == Some(id)))
+    }
+
+    fn update_borders(&mut self) {
+        self.borders = self.border_colors();
+    }
+
+    fn border_colors(&self) -> HashMap<Xid, u32> {
+        let mut res = HashMap::new();
+        for i in 0..self.tags.len() {
+            for ring in self.visible(i) {
+                if let (Some(id), true) = (ring.focus(), ring.len() > 1) {
+                    res.insert(id, HIDDEN_BORDER);
                 }
             }
         }
-        let mut focus_index = None;
-        for (i, col) in col_ring.iter().enumerate() {
-            if let FocusMove::Id(cid) = col {
-                if cid == &fid {
-                    focus_index = Some(i);
+        for id in &self.floating {
+            res.insert(*id, FLOATING_BORDER);
+        }
+        for id in &self.pinned {
+            res.insert(*id, PINNED_BORDER);
+        }
+        if let Some(sid) = self.scratchpad {
+            res.insert(sid, SCRATCHPAD_BORDER);
+        }
+        res
+    }
+
+    fn is_focused_in_a_ring(&self, id: Xid) -> bool {
+        self.ring_of(id).is_some()
+    }
+
+    // ws_labels: tags on screen, in physical order (see screens_in_order)
+    fn move_focus<'a>(&self, move_left: bool, focused: Option<Xid>, current_tag: &str, ws_labels: &'a[String]) -> FocusMove<'a> {
+        let mut col_ring = Vec::new();
+        for ws_label in ws_labels {
+            if let Some(index) = self.tag_indices.get(ws_label) {
+                let ids = self.focuses(*index);
+                // a fullscreen window hides the other columns
+                let full = ids.iter().find(|id| self.fullscreen.contains(id));
+                match full {
+                    Some(fid) => col_ring.push((ws_label, FocusMove::Id(*fid))),
+                    None if ids.is_empty() => col_ring.push((ws_label, FocusMove::Tag(ws_label))),
+                    None => col_ring.extend(ids.into_iter().map(|id| (ws_label, FocusMove::Id(id)))),
                 }
             }
         }
-        if focus_index.is_none() {
-            return FocusMove::Noop;
-        }
+        // without a focused window we are on an empty screen, or nothing on it has focus
+        let focus_index = match focused {
+            Some(fid) => col_ring.iter().position(|(_, col)| matches!(col, FocusMove::Id(cid) if *cid == fid)),
+            None if move_left => col_ring.iter().position(|(tag, _)| *tag == current_tag),
+            None => col_ring.iter().rposition(|(tag, _)| *tag == current_tag),
+        };
         if let Some(fi) = focus_index {
             let l = col_ring.len();
             let nfi = if move_left {
@@ -315,91 +852,351 @@ impl Rings {
             } else {
                 fi + 1
             };
-            col_ring[nfi % l]
+            col_ring[nfi % l].1
         } else {
             FocusMove::Noop
         }
     }
 
-    fn insert(&mut self, id: Xid, focused: Option<Xid>, ws_label: &str) {
-        if let Some(index) = self.tag_indices.get(ws_label) {
-            let (l, r) = &mut self.tags[*index];
-            if l.len() == 0 {
-                l.insert(id);
-            } else if r.len() == 0 || r.focus() == focused {
-                r.insert(id);
+    // fill the first empty column, otherwise swallow the focused one (or the first),
+    // pinned windows are not swallowed: use another column or park behind the focused one
+    // returns true if id is shown
+    fn insert(&mut self, id: Xid, focused: Option<Xid>, ws_label: &str) -> bool {
+        if let Some(index) = self.tag_indices.get(ws_label).copied() {
+            let empty = self.visible(index).iter().position(|r| r.len() == 0);
+            let col = empty
+                .or_else(|| focused.and_then(|fid| self.col_of(index, fid)))
+                .unwrap_or(0);
+            let pinned = |r: &Ring| r.locked || r.focus().is_some_and(|f| self.pinned.contains(&f));
+            let visible = self.visible(index);
+            let col = if pinned(&visible[col]) {
+                (1..visible.len()).map(|d| (col + d) % visible.len()).find(|c| !pinned(&visible[*c]))
             } else {
-                l.insert(id);
+                Some(col)
+            };
+            match col {
+                Some(col) => {
+                    self.tags[index][col].insert(id);
+                    return true;
+                },
+                None => {
+                    let col = focused.and_then(|fid| self.col_of(index, fid)).unwrap_or(0);
+                    self.tags[index][col].park(id);
+                },
+            }
+        }
+        false
+    }
+
+    // the (tag index, column) a template meant a new window for
+    fn claim_pending(&mut self, pid: Option<u32>, class: &str) -> Option<(usize, usize)> {
+        self.pending.retain(|p| p.spawned.elapsed() < TEMPLATE_TIMEOUT);
+        // a window with a pid of its own only matches by class when its program forks
+        let ix = pid.and_then(|pid| self.pending.iter().position(|p| p.pid == pid))
+            .or_else(|| self.pending.iter().position(|p| p.class == class && (pid.is_none() || p.forks)))?;
+        let p = self.pending.remove(ix);
+        Some((p.tag, p.col))
+    }
+
+    // swallow the ring of column col, parked behind its focus when it is locked
+    // returns true if id is shown
+    fn insert_into(&mut self, id: Xid, index: usize, col: usize) -> bool {
+        let col = self.free_col(index, col);
+        let ring = &mut self.tags[index][col];
+        if ring.locked {
+            ring.park(id);
+            false
+        } else {
+            ring.insert(id);
+            true
+        }
+    }
+
+    fn save_view(&mut self, name: &str, ws_label: &str, focus: Option<Xid>) {
+        let index = match self.tag_indices.get(ws_label) { Some(i) => *i, None => return };
+        let shown = self.visible(index).iter().filter_map(|r| r.focus()).collect::<Vec<_>>();
+        let view = View {
+            tag: index,
+            focus: focus.filter(|f| shown.contains(f)),
+            fullscreen: shown.iter().find(|id| self.fullscreen.contains(id)).copied(),
+            monocle: self.monocle.contains(&index),
+            shown,
+        };
+        self.views.insert(name.to_string(), view);
+    }
+
+    // rotates the rings back to the saved windows, windows that are gone or moved
+    // to another tag are skipped
+    fn apply_view(&mut self, view: &View) {
+        for id in &view.shown {
+            if let Some((i, c, p)) = self.locate(*id) {
+                let ring = &mut self.tags[i][c];
+                if i == view.tag && !ring.locked {
+                    ring.focus = p;
+                }
             }
         }
+        if view.monocle {
+            self.monocle.insert(view.tag);
+        } else {
+            self.monocle.remove(&view.tag);
+        }
+    }
+
+    // returns true if the ring of id is now locked
+    fn toggle_lock(&mut self, id: Xid, ws_label: &str) -> bool {
+        let index = match self.tag_indices.get(ws_label) { Some(i) => *i, None => return false };
+        match self.focused_ring_mut(index, id) {
+            Some(ring) => {
+                ring.locked = !ring.locked;
+                ring.locked
+            },
+            None => false,
+        }
+    }
+
+    // returns true if id is now pinned
+    fn toggle_pin(&mut self, id: Xid) -> bool {
+        if !self.pinned.remove(&id) {
+            self.pinned.insert(id);
+            return true;
+        }
+        false
     }
 
     // returns newly focused on id
     fn rotate(&mut self, focused: Xid, ws_label: &str, right: bool) -> Option<Xid> {
-        if let Some(index) = self.tag_indices.get(ws_label) {
-            let (l, r) = &mut self.tags[*index];
-            if l.focus() == Some(focused) {
-                l.rotate(right)
-            } else if r.focus() == Some(focused) {
-                r.rotate(right)
-            } else {
-                None
+        let index = self.tag_indices.get(ws_label).copied()?;
+        let ring = self.focused_ring_mut(index, focused)?;
+        let nfid = if ring.locked { None } else { ring.rotate(right) };
+        self.follow_link(nfid)
+    }
+
+    // returns newly focused on id
+    fn jump(&mut self, focused: Xid, ws_label: &str, index: RingIndex) -> Option<Xid> {
+        let index_tag = self.tag_indices.get(ws_label).copied()?;
+        let ring = self.focused_ring_mut(index_tag, focused)?;
+        let nfid = if ring.locked { None } else { ring.jump(index) };
+        self.follow_link(nfid)
+    }
+
+    // show the partner of a newly shown window in its own ring
+    fn follow_link(&mut self, id: Option<Xid>) -> Option<Xid> {
+        let partner = id.and_then(|id| self.links.get(&id).copied());
+        let here = id.and_then(|id| self.locate(id));
+        // only a partner in another column of the same tag, a merge may have put them in one ring
+        if let (Some((i, c, _)), Some((pi, pc, p))) = (here, partner.and_then(|p| self.locate(p))) {
+            let ring = &mut self.tags[i][pc];
+            if pi == i && pc != c && pc < self.cols[i] && !ring.locked {
+                ring.focus = p;
             }
-        } else {
-            None
+        }
+        id
+    }
+
+    // links id with the window shown in the next column (the previous one for the last column),
+    // or unlinks it, returns true if id is now linked
+    fn toggle_link(&mut self, id: Xid, ws_label: &str) -> bool {
+        if let Some(partner) = self.links.remove(&id) {
+            self.links.remove(&partner);
+            return false;
+        }
+        let index = match self.tag_indices.get(ws_label) { Some(i) => *i, None => return false };
+        let col = match self.col_of(index, id) { Some(c) => c, None => return false };
+        let shown = self.visible(index).iter().map(|r| r.focus()).collect::<Vec<_>>();
+        let other = shown.get(col + 1).or_else(|| col.checked_sub(1).and_then(|c| shown.get(c)));
+        match other.copied().flatten() {
+            Some(partner) => {
+                self.unlink(partner);
+                self.links.insert(id, partner);
+                self.links.insert(partner, id);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn unlink(&mut self, id: Xid) {
+        if let Some(partner) = self.links.remove(&id) {
+            self.links.remove(&partner);
         }
     }
 
+    // show the next linked pair of the tag, ordered by the place of their left member
+    // returns the member to focus
+    fn rotate_pairs(&mut self, focused: Option<Xid>, ws_label: &str) -> Option<Xid> {
+        let index = self.tag_indices.get(ws_label).copied()?;
+        let mut pairs = self.links.iter()
+            .filter_map(|(a, b)| {
+                let (la, lb) = (self.locate(*a)?, self.locate(*b)?);
+                (la.0 == index && lb.0 == index && la.1 < lb.1).then_some((la, lb))
+            })
+            .collect::<Vec<_>>();
+        if pairs.is_empty() { return None; }
+        pairs.sort();
+        let current = focused.and_then(|f| self.links.get(&f).map(|p| (f, *p)))
+            .and_then(|(f, p)| {
+                let (lf, lp) = (self.locate(f)?, self.locate(p)?);
+                pairs.iter().position(|pair| *pair == (lf, lp) || *pair == (lp, lf))
+            });
+        let next = current.map_or(0, |c| (c + 1) % pairs.len());
+        let ((_, ca, pa), (_, cb, pb)) = pairs[next];
+        if self.tags[index][ca].locked || self.tags[index][cb].locked { return None; }
+        self.tags[index][ca].focus = pa;
+        self.tags[index][cb].focus = pb;
+        let focused_col = focused.and_then(|f| self.col_of(index, f));
+        let focus = if focused_col == Some(cb) { cb } else { ca };
+        self.tags[index][focus].focus()
+    }
+
     // returns Option<to be focused id>
     fn delete(&mut self, id: Xid) -> Option<Xid> {
-        for (i, _) in self.tag_names.iter().enumerate() {
-            let (l, r) = &mut self.tags[i];
-            match l.delete(id) {
-                // currently it is illegal to have a client in multiple rings
+        let mut emptied_sticky = None;
+        for (si, (_, ring)) in self.sticky.iter_mut() {
+            match ring.delete(id) {
                 (false, Some(fid)) => return Some(fid),
-                (true, None) => {
-                    std::mem::swap(l, r);
-                    return l.focus();
-                }
+                (true, _) => emptied_sticky = Some(*si),
                 _ => { },
             }
-            match r.delete(id) {
-                (_, Some(fid)) => return Some(fid),
-                (true, _) => return l.focus(),
-                _ => { },
+        }
+        if let Some(si) = emptied_sticky {
+            // the tags on this screen get the column back
+            self.sticky.remove(&si);
+            let index = self.screen_of.iter().position(|s| *s == Some(si));
+            return index.and_then(|i| self.focuses(i).first().copied());
+        }
+        for i in 0..self.tags.len() {
+            for c in 0..self.tags[i].len() {
+                match self.tags[i][c].delete(id) {
+                    // currently it is illegal to have a client in multiple rings
+                    (false, Some(fid)) => return Some(fid),
+                    (true, _) => {
+                        // close the gap, columns to the right shift left
+                        self.remove_col(i, c);
+                        let rings = &self.tags[i];
+                        let left = if c > 0 { rings[c - 1].focus() } else { None };
+                        return rings[c].focus().or(left);
+                    },
+                    _ => { },
+                }
+            }
+        }
+        // not on screen, nothing to focus
+        for activity in self.activities.values_mut() {
+            for i in 0..activity.tags.len() {
+                let rings = &mut activity.tags[i];
+                if let Some(c) = rings.iter().position(|r| r.ring.contains(&id)) {
+                    if rings[c].delete(id).0 {
+                        rings.remove(c);
+                        rings.push(Ring::default());
+                        merged_col_removed(&mut activity.merged[i], c);
+                    }
+                    return None;
+                }
             }
         }
         None
     }
 
-    // returns true if a swap occured
-    fn swap_cols(&mut self, ws_label: &str) -> bool {
-        if let Some(index) = self.tag_indices.get(ws_label) {
-            let (l, r) = &mut self.tags[*index];
-            if l.len() > 0 && r.len() > 0 {
-                std::mem::swap(l, r);
-                return true;
+    // parks the rings of the current activity and takes over those of name,
+    // a new activity starts out empty, returns false if name is already active
+    fn switch_activity(&mut self, name: &str) -> bool {
+        if name == self.activity { return false; }
+        // the scratchpad belongs to no activity
+        if let Some(sid) = self.scratchpad {
+            if self.is_focused_in_a_ring(sid) {
+                self.delete(sid);
             }
         }
-        false
+        let next = self.activities.remove(name).unwrap_or_else(|| Activity::new(self.cols));
+        let cols = self.cols;
+        let prev = Activity {
+            tags: std::mem::replace(&mut self.tags, next.tags),
+            cols: std::mem::replace(&mut self.cols, next.cols),
+            merged: std::mem::replace(&mut self.merged, next.merged),
+            active_col: std::mem::replace(&mut self.active_col, next.active_col),
+            monocle: std::mem::replace(&mut self.monocle, next.monocle),
+        };
+        let old = std::mem::replace(&mut self.activity, name.to_string());
+        self.activities.insert(old, prev);
+        // the screens may have changed columns while it was parked
+        for (i, n) in cols.into_iter().enumerate() {
+            self.set_columns(i, n);
+        }
+        self.last_focus = None;
+        true
+    }
+
+    // moves id into the same tag of another activity, swallowing the column that was active there
+    // returns false if id is not in a ring of the current activity
+    fn send_to_activity(&mut self, id: Xid, name: &str) -> bool {
+        if name == self.activity || self.scratchpad == Some(id) { return false; }
+        let index = match self.locate(id) { Some((i, _, _)) => i, None => return false };
+        self.delete(id);
+        self.unlink(id);
+        let cols = self.cols;
+        let activity = self.activities.entry(name.to_string()).or_insert_with(|| Activity::new(cols));
+        let col = activity.active_col[index];
+        activity.tags[index][col].insert(id);
+        true
+    }
+
+    // swaps the focused column with its neighbour, returns true if a swap occured
+    fn swap_cols(&mut self, focused: Option<Xid>, ws_label: &str, right: bool) -> bool {
+        let index = match self.tag_indices.get(ws_label) { Some(i) => *i, None => return false };
+        let n = self.visible(index).iter().filter(|r| r.len() > 0).count();
+        let col = focused.and_then(|fid| self.col_of(index, fid)).unwrap_or(0);
+        if n < 2 || col >= n || self.tags[index][col].locked { return false; }
+        // locked columns stay put, swap with the nearest unlocked one instead
+        let other = (1..n)
+            .map(|d| if right { (col + d) % n } else { (col + n - d) % n })
+            .find(|c| !self.tags[index][*c].locked);
+        let other = match other { Some(o) => o, None => return false };
+        self.tags[index].swap(col, other);
+        self.active_col[index] = other;
+        true
     }
 
     fn swap_ring(&mut self, focused: Option<Xid>, ws_label: &str, right: bool) {
-        if focused.is_none() { return; }
-        if let Some(index) = self.tag_indices.get(ws_label) {
-            let (l, r) = &mut self.tags[*index];
-            if l.focus() == focused {
-                l.swap(right)
-            } else if r.focus() == focused {
-                r.swap(right)
+        let fid = match focused { Some(fid) => fid, None => return };
+        if let Some(index) = self.tag_indices.get(ws_label).copied() {
+            if let Some(ring) = self.focused_ring_mut(index, fid) {
+                ring.swap(right);
             }
         }
     }
 }
 
+// column c of a tag is gone, the rings merged into it went along with it
+fn merged_col_removed(merged: &mut Vec<(usize, Ring)>, c: usize) {
+    merged.retain(|(host, _)| *host != c);
+    for (host, _) in merged.iter_mut() {
+        if *host > c { *host -= 1; }
+    }
+}
+
 fn rebuild(rings: Arc<RefCell<Rings>>, cs: &mut StackSet<Xid>) {
     println!("rebuild!");
+    // tags on screen use the column count and orientation of that screen
+    let screens = cs.screens()
+        .map(|s| (s.workspace.tag().to_string(), s.index(), s.geometry()))
+        .collect::<Vec<_>>();
+    rings.borrow_mut().screen_of = Default::default();
+    for (tag, screen_index, r) in screens {
+        let index = rings.borrow().tag_indices.get(&tag).copied();
+        if let Some(index) = index {
+            let stacked = screen_stacked(screen_index, r);
+            let n = rings.borrow().wanted_columns(screen_index);
+            rings.borrow_mut().screen_of[index] = Some(screen_index);
+            rings.borrow_mut().set_columns(index, n);
+            rings.borrow_mut().stacked[index] = stacked;
+            if let Some(ws) = cs.workspace_mut(&tag) {
+                ws.handle_message(SetStacked(stacked));
+            }
+        }
+    }
     let rings_state = rings.borrow().current_view();
+    rings.borrow_mut().update_borders();
 
     for (tname, tview) in rings_state {
         println!("  {tname}");
@@ -407,7 +1204,7 @@ fn rebuild(rings: Arc<RefCell<Rings>>, cs: &mut StackSet<Xid>) {
         for xid in wcs {
             cs.move_client_to_tag(&xid, "reikai");
         }
-        let mut put_on_screen = Vec::with_capacity(2);
+        let mut put_on_screen = Vec::with_capacity(MAX_COLS);
         for xid in tview.into_iter().rev() {
             if rings.borrow().fullscreen.contains(&xid) {
                 put_on_screen.clear();
@@ -421,6 +1218,15 @@ fn rebuild(rings: Arc<RefCell<Rings>>, cs: &mut StackSet<Xid>) {
             println!("    {xid}");
         }
     }
+
+    let (sid, float) = {
+        let r = rings.borrow();
+        (r.scratchpad, r.scratchpad_float.clone())
+    };
+    if let (Some(sid), Some((tag, r))) = (sid, float) {
+        cs.move_client_to_tag(&sid, &tag);
+        let _ = cs.float(sid, r);
+    }
 }
 
 // hooks
@@ -436,34 +1242,121 @@ fn og_window_size_manage<X: XConn + 'static>(id: Xid, state: &mut State<X>, x: &
 fn rings_manage<X: XConn + 'static>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
     if x.query_or(false, &AppName("shapebar"), id) { return Ok(()) }
     let rings = state.extension::<Rings>()?;
+    if rings.borrow().scratchpad.is_none() && x.query_or(false, &AppName(SCRATCHPAD_INSTANCE), id) {
+        rings.borrow_mut().scratchpad = Some(id);
+        rings.borrow_mut().update_borders();
+    }
+    let slot = if rings.borrow().pending.is_empty() { None } else {
+        let pid = match x.get_prop(id, "_NET_WM_PID") {
+            Ok(Some(Prop::Cardinal(v))) => v.first().copied(),
+            _ => None,
+        };
+        rings.borrow_mut().claim_pending(pid, &window_class(x, id))
+    };
     let cs = &mut state.client_set;
     let ws = cs.current_workspace();
     let fc = rings.borrow().last_focus;
-    rings.borrow_mut().insert(id, fc, ws.tag());
+    let shown = match slot {
+        // a template placed on a tag that is no longer focused doesn't take focus
+        Some((index, col)) => {
+            rings.borrow_mut().insert_into(id, index, col) && rings.borrow().tag_names[index] == ws.tag()
+        },
+        None => rings.borrow_mut().insert(id, fc, ws.tag()),
+    };
+    if shown {
+        rings.borrow_mut().set_focus(id);
+    }
     rebuild(rings.clone(), cs);
-    cs.focus_client(&id);
-    rings.borrow_mut().last_focus = Some(id);
+    match (shown, fc) {
+        (true, _) => cs.focus_client(&id),
+        (false, Some(fid)) => cs.focus_client(&fid),
+        _ => { },
+    }
     Ok(())
 }
 
-pub fn rings_refresh<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
-    let rings = state.extension::<Rings>()?;
+fn dim_unfocused<X: XConn>(rings: &RefCell<Rings>, state: &State<X>, x: &X, opacity: f32) -> Result<()> {
     let focus = state.client_set.current_client().copied();
-    if focus != rings.borrow().last_focus {
-        rings.borrow_mut().last_focus = focus;
+    let level = (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64) as u32;
+    for ws in state.client_set.on_screen_workspaces() {
+        for &id in ws.clients() {
+            let dim = Some(id) != focus
+                && !NO_DIM_CLASSES.iter().any(|c| x.query_or(false, &ClassName(c), id));
+            let dimmed = rings.borrow().dimmed.contains(&id);
+            if dim && !dimmed {
+                x.set_prop(id, "_NET_WM_WINDOW_OPACITY", Prop::Cardinal(vec![level]))?;
+                rings.borrow_mut().dimmed.insert(id);
+            } else if !dim && dimmed {
+                x.delete_prop(id, "_NET_WM_WINDOW_OPACITY")?;
+                rings.borrow_mut().dimmed.remove(&id);
+            }
+        }
     }
     Ok(())
 }
 
-pub fn rings_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+// penrose only knows focused and normal borders, this runs after it has set those
+fn state_borders<X: XConn>(rings: &RefCell<Rings>, state: &State<X>, x: &X) -> Result<()> {
+    let focus = state.client_set.current_client().copied();
+    let rings = rings.borrow();
+    for ws in state.client_set.on_screen_workspaces() {
+        for &id in ws.clients() {
+            let color = match rings.borders.get(&id) {
+                Some(c) if Some(id) != focus || rings.scratchpad == Some(id) => Color::new_from_hex(*c),
+                _ if Some(id) == focus => state.config.focused_border,
+                _ => state.config.normal_border,
+            };
+            x.set_client_border_color(id, color)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn rings_refresh<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    let focus = state.client_set.current_client().copied();
+    if focus != rings.borrow().last_focus {
+        match focus {
+            Some(id) => rings.borrow_mut().set_focus(id),
+            None => rings.borrow_mut().last_focus = None,
+        }
+    }
+    state_borders(&rings, state, x)?;
+    if let Some(opacity) = INACTIVE_OPACITY {
+        dim_unfocused(&rings, state, x, opacity)?;
+    }
+    // a tag was moved to a screen with a different number of columns or orientation
+    let stale = state.client_set.screens().any(|s| {
+        let r = rings.borrow();
+        r.tag_indices.get(s.workspace.tag()).is_some_and(|i| {
+            r.cols[*i] != r.wanted_columns(s.index())
+                || r.screen_of[*i] != Some(s.index())
+                || r.stacked[*i] != screen_stacked(s.index(), s.geometry())
+        })
+    });
+    if stale {
+        rebuild(rings, &mut state.client_set);
+        x.refresh(state)?;
+    }
+    Ok(())
+}
+
+pub fn rings_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
     let rings = state.extension::<Rings>()?;
     let cs = &mut state.client_set;
     match event {
         XEvent::Destroy(id) => {
+            rings.borrow_mut().dimmed.remove(id);
+            rings.borrow_mut().floating.remove(id);
+            rings.borrow_mut().pinned.remove(id);
+            rings.borrow_mut().unlink(*id);
             let sid = rings.borrow().scratchpad;
             if let Some(sid) = sid {
                 if sid == *id {
                     rings.borrow_mut().scratchpad = None;
+                    rings.borrow_mut().scratchpad_origin = None;
+                    rings.borrow_mut().scratchpad_float = None;
+                    rings.borrow_mut().scratchpad_return = None;
                 }
             }
             let res = rings.borrow_mut().delete(*id);
@@ -501,96 +1394,345 @@ pub fn rings_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x:
     Ok(true)
 }
 
+// no border when a window is alone on its screen (this includes fullscreen windows),
+// floating windows don't count just like they don't for the gaps of Cols
+pub fn smart_borders_refresh<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let border = state.config.border_width;
+    let widths = state.extension::<BorderWidths>()?;
+    let cs = &state.client_set;
+    widths.borrow_mut().map.retain(|id, _| cs.contains(id));
+    for ws in cs.on_screen_workspaces() {
+        let tiled = ws.clients().filter(|id| !cs.is_floating(id)).count();
+        let bw = if tiled > 1 { border } else { 0 };
+        for id in ws.clients() {
+            if widths.borrow_mut().map.insert(*id, bw) != Some(bw) {
+                x.set_client_config(*id, &[ClientConfig::BorderPx(bw)])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn struts_startup<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let struts = state.extension::<Struts>()?;
+    for id in x.existing_clients()? {
+        if let Some(strut) = read_strut(x, id) {
+            // bars started before the wm, see struts_event
+            x.set_client_attributes(id, &[ClientAttr::ClientEventMask])?;
+            struts.borrow_mut().map.insert(id, strut);
+        }
+    }
+    Ok(())
+}
+
+// keep track of docks appearing, changing and disappearing
+pub fn struts_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    let struts = state.extension::<Struts>()?;
+    let changed = match event {
+        XEvent::MapRequest(id) => match read_strut(x, *id) {
+            Some(strut) => {
+                // docks are not always managed, but we want to hear about their struts changing
+                x.set_client_attributes(*id, &[ClientAttr::ClientEventMask])?;
+                struts.borrow_mut().map.insert(*id, strut);
+                true
+            },
+            None => false,
+        },
+        XEvent::PropertyNotify(p) if p.atom.starts_with("_NET_WM_STRUT") => {
+            match read_strut(x, p.id) {
+                Some(strut) => struts.borrow_mut().map.insert(p.id, strut) != Some(strut),
+                None => struts.borrow_mut().map.remove(&p.id).is_some(),
+            }
+        },
+        XEvent::Destroy(id) | XEvent::UnmapNotify(id) => struts.borrow_mut().map.remove(id).is_some(),
+        _ => false,
+    };
+    if changed {
+        x.refresh(state)?;
+    }
+    Ok(true)
+}
+
+// send every on screen tag the space its screen needs to keep free
+pub fn struts_refresh<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let struts = state.extension::<Struts>()?;
+    let root = state.client_set.screens()
+        .map(|s| s.geometry())
+        .fold((0, 0), |(w, h), r| (w.max(r.x + r.w), h.max(r.y + r.h)));
+    let wanted = state.client_set.screens()
+        .map(|s| (s.workspace.tag().to_string(), reserved_for(s.geometry(), root, &struts.borrow().map)))
+        .collect::<Vec<_>>();
+    let mut stale = false;
+    for (tag, res) in wanted {
+        if struts.borrow().applied.get(&tag) == Some(&res) { continue; }
+        if let Some(ws) = state.client_set.workspace_mut(&tag) {
+            ws.handle_message(res);
+        }
+        struts.borrow_mut().applied.insert(tag, res);
+        stale = true;
+    }
+    if stale {
+        x.refresh(state)?;
+    }
+    Ok(())
+}
+
+pub fn screens_refresh<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+    let mem = state.extension::<ScreenMemory>()?;
+    let cs = &state.client_set;
+    let rects = cs.screens().map(|s| s.geometry()).collect::<Vec<_>>();
+    let tags = cs.screens().map(|s| s.workspace.tag().to_string()).collect::<Vec<_>>();
+    mem.borrow_mut().map.insert(screen_key(&rects), tags);
+    Ok(())
+}
+
+// monitor (un)plugged: rings live per tag so they survive as is, only the tags need
+// to go back to the screens they were on the last time this monitor setup was seen
+pub fn screens_event<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    if !matches!(event, XEvent::RandrNotify) { return Ok(true); }
+    let rects = x.screen_details()?;
+    if rects.is_empty() { return Ok(false); }
+    let mem = state.extension::<ScreenMemory>()?;
+    let rings = state.extension::<Rings>()?;
+    let cs = &mut state.client_set;
+    let focused = cs.current_client().copied();
+    cs.update_screens(rects.clone())?;
+
+    let remembered = mem.borrow().map.get(&screen_key(&rects)).cloned();
+    if let Some(tags) = remembered {
+        let focused_screen = cs.current_screen().index();
+        for (i, tag) in tags.iter().enumerate() {
+            if i >= rects.len() { break; }
+            cs.focus_screen(i);
+            cs.pull_tag_to_screen(tag);
+        }
+        cs.focus_screen(focused_screen);
+    }
+
+    rebuild(rings, cs);
+    if let Some(fid) = focused {
+        cs.focus_client(&fid);
+    }
+    x.refresh(state)?;
+    Ok(false)
+}
+
+fn window_class<X: XConn>(x: &X, id: Xid) -> String {
+    match x.get_prop(id, "WM_CLASS") {
+        Ok(Some(Prop::UTF8String(strs))) if strs.len() > 1 => strs[1].clone(),
+        _ => String::new(),
+    }
+}
+
 // actions
 
+fn show_ring_overlay<X: XConn>(state: &State<X>, x: &X, id: Xid) -> Result<()> {
+    let timeout = match RING_OVERLAY { Some(t) => t, None => return Ok(()) };
+    let rings = state.extension::<Rings>()?;
+    let overlay = state.extension::<RingOverlay>()?;
+    let (members, focus) = match rings.borrow().ring_of(id) {
+        Some(ring) => (ring.ring.clone(), ring.focus),
+        None => return Ok(()),
+    };
+    let titles = members.iter().map(|id| x.window_title(*id).unwrap_or_default()).collect();
+    let screen = state.client_set.current_screen().geometry();
+    overlay.borrow_mut().show(titles, focus, screen, timeout);
+    Ok(())
+}
+
+// screens side by side are ordered left to right, stacked screens top to bottom
+fn screens_in_order(cs: &StackSet<Xid>) -> Vec<String> {
+    order_screens(cs.screens().map(|s| (s.geometry(), s.workspace.tag().to_string())).collect())
+}
+
+fn order_screens(mut screens: Vec<(Rect, String)>) -> Vec<String> {
+    let mid = |r: &Rect| r.x as i64 + r.w as i64 / 2;
+    screens.sort_by_key(|(r, _)| mid(r));
+    // a screen whose middle lies within the previous screen's width is stacked with it
+    let mut col = 0;
+    let mut keyed = Vec::with_capacity(screens.len());
+    for (i, (r, tag)) in screens.iter().enumerate() {
+        if i > 0 {
+            let p = &screens[i - 1].0;
+            if mid(r) >= p.x as i64 + p.w as i64 { col += 1; }
+        }
+        keyed.push(((col, r.y), tag.clone()));
+    }
+    keyed.sort_by_key(|(k, _)| *k);
+    keyed.into_iter().map(|(_, tag)| tag).collect()
+}
+
+fn focus_column<X: XConn>(state: &mut State<X>, left: bool) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    let cs = &mut state.client_set;
+    let wss = screens_in_order(cs);
+    let fc = cs.current_client().copied();
+    let wstag = cs.current_workspace().tag().to_string();
+    let fm = rings.borrow().move_focus(left, fc, &wstag, &wss);
+    match fm {
+        FocusMove::Noop => { },
+        FocusMove::Id(nfid) => {
+            // in monocle mode the other column is not on screen yet
+            rings.borrow_mut().set_focus(nfid);
+            rebuild(rings.clone(), cs);
+            cs.focus_client(&nfid);
+        },
+        FocusMove::Tag(ws) => cs.focus_tag(ws),
+    }
+    Ok(())
+}
+
 fn move_focus<X: XConn>(left: bool) -> Box<dyn KeyEventHandler<X>> {
     key_handler(move |state, x: &X| {
-        let rings = state.extension::<Rings>()?;
-        let cs = &mut state.client_set;
-        let wss = cs.on_screen_workspaces().map(|ws| ws.tag().to_string()).collect::<Vec<_>>();
-        let fc = cs.current_client().copied();
-        if let Some(fid) = fc {
-            match rings.borrow().move_focus(left, fid, &wss) {
-                FocusMove::Noop => { },
-                FocusMove::Id(nfid) => cs.focus_client(&nfid),
-                FocusMove::Tag(ws) => cs.focus_tag(ws),
+        focus_column(state, left)?;
+        x.refresh(state)
+    })
+}
+
+// on the scratchpad: unsummon it first, then only move on if keep_going is set
+fn ring_move<X: XConn>(
+    state: &mut State<X>,
+    x: &X,
+    keep_going: bool,
+    op: impl Fn(&mut Rings, Xid, &str) -> Option<Xid>,
+) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    let sid = rings.borrow().scratchpad;
+    let cs = &mut state.client_set;
+    let wstag = cs.current_workspace().tag().to_string();
+    let fc = cs.current_client().copied();
+    if let Some(mut fid) = fc {
+        if let Some(sid) = sid {
+            if fid == sid {
+                let res = rings.borrow_mut().delete(sid);
+                if let Some(nfid) = res {
+                    rebuild(rings.clone(), cs);
+                    cs.focus_client(&nfid);
+                    if !keep_going {
+                        return x.refresh(state);
+                    }
+                    fid = nfid;
+                }
             }
+        }
+        let nfid = op(&mut rings.borrow_mut(), fid, &wstag);
+        rebuild(rings.clone(), cs);
+        if let Some(nfid) = nfid {
+            cs.focus_client(&nfid);
         } else {
-            cs.next_screen();
-            let wstag = cs.current_workspace().tag().to_string();
-            if let Some(nfid) = rings.borrow().get_left_or_right_or_only(!left, &wstag) {
-                cs.focus_client(&nfid);
-            }
+            cs.focus_client(&fid);
         }
-        x.refresh(state)
-    })
+        x.refresh(state)?;
+        return show_ring_overlay(state, x, nfid.unwrap_or(fid));
+    }
+    Ok(())
 }
 
 fn ring_rotate<X: XConn>(right: bool) -> Box<dyn KeyEventHandler<X>> {
     key_handler(move |state, x: &X| {
+        ring_move(state, x, false, |rings, fid, tag| rings.rotate(fid, tag, right))
+    })
+}
+
+fn ring_jump<X: XConn>(index: RingIndex) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        ring_move(state, x, true, |rings, fid, tag| rings.jump(fid, tag, index))
+    })
+}
+
+fn swap_cols<X: XConn>(right: bool) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X|{
         let rings = state.extension::<Rings>()?;
-        let sid = rings.borrow().scratchpad;
         let cs = &mut state.client_set;
         let wstag = cs.current_workspace().tag().to_string();
         let fc = cs.current_client().copied();
-        if let Some(fid) = fc {
-            if let Some(sid) = sid {
-                if fid == sid {
-                    let res = rings.borrow_mut().delete(sid);
-                    if let Some(nfid) = res {
-                        rebuild(rings.clone(), cs);
-                        cs.focus_client(&nfid);
-                        return x.refresh(state);
-                    }
-                }
-            }
-            let nfid = rings.borrow_mut().rotate(fid, &wstag, right);
+        let need_swap = rings.borrow_mut().swap_cols(fc, &wstag, right);
+        if need_swap {
             rebuild(rings.clone(), cs);
-            if let Some(nfid) = nfid {
-                cs.focus_client(&nfid);
-            } else {
+            if let Some(fid) = fc {
                 cs.focus_client(&fid);
             }
-            return x.refresh(state);
+            let _ = x.refresh(state);
         }
         Ok(())
     })
 }
 
-fn swap_cols<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+fn swap_ring<X: XConn>(right: bool) -> Box<dyn KeyEventHandler<X>> {
     key_handler(move |state, x: &X|{
         let rings = state.extension::<Rings>()?;
         let cs = &mut state.client_set;
         let wstag = cs.current_workspace().tag();
-        let need_swap = rings.borrow_mut().swap_cols(wstag);
-        if need_swap {
-            cs.swap_down();
-            let _ = x.refresh(state);
+        let fc = cs.current_client().copied();
+        rings.borrow_mut().swap_ring(fc, wstag, right);
+        match fc {
+            Some(fid) => show_ring_overlay(state, x, fid),
+            None => Ok(()),
         }
-        Ok(())
     })
 }
 
-fn swap_ring<X: XConn>(right: bool) -> Box<dyn KeyEventHandler<X>> {
-    key_handler(move |state, _: &X|{
+// focus goes back to where it was before summoning, if that window is still on the tag
+fn hide_floating_scratchpad(rings: Arc<RefCell<Rings>>, cs: &mut StackSet<Xid>) {
+    let sid = rings.borrow().scratchpad;
+    if let Some(sid) = sid {
+        rings.borrow_mut().scratchpad_float = None;
+        cs.sink(&sid);
+        cs.move_client_to_tag(&sid, "reikai");
+    }
+    let back = rings.borrow_mut().scratchpad_return.take()
+        .filter(|id| cs.current_workspace().clients().any(|c| c == id))
+        .or_else(|| cs.current_workspace().clients().next().copied());
+    if let Some(fid) = back {
+        rings.borrow_mut().set_focus(fid);
+        cs.focus_client(&fid);
+    }
+}
+
+// the scratchpad floats centered above the columns, the rings are left alone
+fn toggle_scratchpad_floating<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
         let rings = state.extension::<Rings>()?;
+        let sid = if let Some(sid) = rings.borrow().scratchpad { sid }
+        else { return spawn_cmd(SCRATCHPAD_CMD); };
+        let ows = state.extension::<OgWindowSize>()?;
         let cs = &mut state.client_set;
-        let wstag = cs.current_workspace().tag();
-        let fc = cs.current_client().copied();
-        rings.borrow_mut().swap_ring(fc, wstag, right);
-        Ok(())
+        if rings.borrow().scratchpad_float.is_some() {
+            hide_floating_scratchpad(rings.clone(), cs);
+            return x.refresh(state);
+        }
+        // summoned into a ring, or a hidden member of one: take it out first
+        rings.borrow_mut().delete(sid);
+        let (w, h) = SCRATCHPAD_FLOAT_SIZE
+            .or_else(|| ows.borrow().map.get(&sid).copied())
+            .unwrap_or((512, 512));
+        let screen_rect = cs.current_screen().geometry();
+        let r = Rect { x: 0, y: 0, w: w.min(screen_rect.w), h: h.min(screen_rect.h) };
+        let r = r.centered_in(&screen_rect).unwrap_or(r);
+        let wstag = cs.current_workspace().tag().to_string();
+        rings.borrow_mut().scratchpad_float = Some((wstag, r));
+        rings.borrow_mut().scratchpad_return = cs.current_client().copied().filter(|id| *id != sid);
+        rebuild(rings.clone(), cs);
+        cs.focus_client(&sid);
+        x.refresh(state)
     })
 }
 
+// summons onto the focused screen, when shown on another screen it moves over
 fn toggle_scratchpad<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(move |state, x: &X| {
         let rings = state.extension::<Rings>()?;
         let sid = if let Some(sid) = rings.borrow().scratchpad { sid }
-        else { return Ok(()); };
+        else { return spawn_cmd(SCRATCHPAD_CMD); };
         let cs = &mut state.client_set;
+        if rings.borrow().scratchpad_float.is_some() {
+            hide_floating_scratchpad(rings.clone(), cs);
+            return x.refresh(state);
+        }
         let on = rings.borrow().is_focused_in_a_ring(sid);
         let wstag = cs.current_workspace().tag().to_string();
         let focused = cs.current_client().copied();
+        let elsewhere = on && cs.current_workspace().clients().all(|id| *id != sid);
         if on {
             let res = rings.borrow_mut().delete(sid);
             rebuild(rings.clone(), cs);
@@ -601,37 +1743,141 @@ fn toggle_scratchpad<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
                 }
             }
             if let Some(nfid) = res {
-                cs.focus_client(&nfid);
-                rings.borrow_mut().last_focus = Some(nfid);
+                if !elsewhere {
+                    cs.focus_client(&nfid);
+                    rings.borrow_mut().set_focus(nfid);
+                }
                 refresh = true;
             }
-            if refresh {
+            if refresh && !elsewhere {
                 return x.refresh(state);
             }
         }
-        // let _ = rings.borrow_mut().delete(sid);
-        rings.borrow_mut().insert(sid, focused, &wstag);
+        // still a hidden member of the ring it was linked in (or rotated away from),
+        // take it out so it never ends up in two rings
+        rings.borrow_mut().delete(sid);
+        if !rings.borrow_mut().insert(sid, focused, &wstag) {
+            // everything on screen is pinned
+            rings.borrow_mut().delete(sid);
+            return Ok(());
+        }
+        rings.borrow_mut().set_focus(sid);
         rebuild(rings.clone(), cs);
         cs.focus_client(&sid);
-        rings.borrow_mut().last_focus = Some(sid);
         x.refresh(state)
     })
 }
 
-fn link_scratchpad<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+fn toggle_sticky_column<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let cs = &mut state.client_set;
+        let wstag = cs.current_workspace().tag().to_string();
+        let si = cs.current_screen().index();
+        if let Some(fid) = cs.current_client().copied() {
+            rings.borrow_mut().toggle_sticky(fid, &wstag, si);
+            rebuild(rings.clone(), cs);
+            cs.focus_client(&fid);
+            return x.refresh(state);
+        }
+        Ok(())
+    })
+}
+
+fn toggle_column_lock<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, _: &X| {
+        let rings = state.extension::<Rings>()?;
+        let cs = &state.client_set;
+        if let Some(fid) = cs.current_client().copied() {
+            rings.borrow_mut().toggle_lock(fid, cs.current_workspace().tag());
+        }
+        Ok(())
+    })
+}
+
+fn toggle_link<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(move |state, _: &X| {
+        let rings = state.extension::<Rings>()?;
+        let cs = &state.client_set;
+        if let Some(fid) = cs.current_client().copied() {
+            rings.borrow_mut().toggle_link(fid, cs.current_workspace().tag());
+        }
+        Ok(())
+    })
+}
+
+fn rotate_pairs<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let cs = &mut state.client_set;
+        let wstag = cs.current_workspace().tag().to_string();
+        let fc = cs.current_client().copied();
+        let nfid = rings.borrow_mut().rotate_pairs(fc, &wstag);
+        if let Some(nfid) = nfid {
+            rings.borrow_mut().set_focus(nfid);
+            rebuild(rings.clone(), cs);
+            cs.focus_client(&nfid);
+            return x.refresh(state);
+        }
+        Ok(())
+    })
+}
+
+fn toggle_pin<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        if let Some(fid) = state.client_set.current_client().copied() {
+            rings.borrow_mut().toggle_pin(fid);
+            rings.borrow_mut().update_borders();
+            return x.refresh(state);
+        }
+        Ok(())
+    })
+}
+
+fn toggle_monocle<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let cs = &mut state.client_set;
+        let wstag = cs.current_workspace().tag().to_string();
+        let fc = cs.current_client().copied();
+        rings.borrow_mut().toggle_monocle(&wstag);
+        rebuild(rings.clone(), cs);
+        if let Some(fid) = fc {
+            cs.focus_client(&fid);
+        }
+        x.refresh(state)
+    })
+}
+
+fn link_scratchpad<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
         let rings = state.extension::<Rings>()?;
         let cs = &mut state.client_set;
         let focused = cs.current_client().copied();
         if let Some(fid) = focused {
             let sp = rings.borrow().scratchpad;
-            if let Some(sp) = sp {
-                if sp == fid {
-                    rings.borrow_mut().scratchpad = None;
-                    return Ok(());
+            if sp == Some(fid) {
+                rings.borrow_mut().scratchpad = None;
+                // it was a regular ring member before, return it there
+                let origin = rings.borrow_mut().scratchpad_origin.take();
+                if let Some(origin) = origin {
+                    let res = rings.borrow_mut().delete(fid);
+                    rings.borrow_mut().insert_at(fid, origin);
+                    rebuild(rings.clone(), cs);
+                    if rings.borrow().is_focused_in_a_ring(fid) {
+                        cs.focus_client(&fid);
+                    } else if let Some(nfid) = res {
+                        cs.focus_client(&nfid);
+                    }
                 }
+            } else {
+                let origin = rings.borrow().locate(fid);
+                rings.borrow_mut().scratchpad = Some(fid);
+                rings.borrow_mut().scratchpad_origin = origin;
             }
-            rings.borrow_mut().scratchpad = Some(fid);
+            rings.borrow_mut().update_borders();
+            return x.refresh(state);
         }
         Ok(())
     })
@@ -651,10 +1897,279 @@ pub fn toggle_floating_focused_remember<X: XConn>() -> Box<dyn KeyEventHandler<X
         let r = r.centered_in(&screen_rect).unwrap_or(r);
 
         let _ = state.client_set.toggle_floating_state(id, r);
+        let rings = state.extension::<Rings>()?;
+        if !rings.borrow_mut().floating.remove(&id) {
+            rings.borrow_mut().floating.insert(id);
+        }
+        rings.borrow_mut().update_borders();
         x.refresh(state)
     })
 }
 
+// pick any managed window, rotate its ring to it and show its tag (and activity),
+// windows in reikai are inserted into the focused ring
+fn switch_window<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    let mut lines = Vec::new();
+    {
+        let r = rings.borrow();
+        for (i, cols) in r.tags.iter().enumerate() {
+            for (c, ring) in cols.iter().enumerate().take(r.cols[i]) {
+                for &id in &ring.ring {
+                    lines.push((format!("{}:{c}", r.tag_names[i]), id));
+                }
+            }
+        }
+        for (si, (c, ring)) in r.sticky.iter() {
+            for &id in &ring.ring {
+                lines.push((format!("s{si}:{c}"), id));
+            }
+        }
+        let mut activities = r.activities.iter().collect::<Vec<_>>();
+        activities.sort_by_key(|(name, _)| name.as_str());
+        for (name, activity) in activities {
+            for (i, cols) in activity.tags.iter().enumerate() {
+                for (c, ring) in cols.iter().enumerate() {
+                    for &id in &ring.ring {
+                        lines.push((format!("{name}:{}:{c}", r.tag_names[i]), id));
+                    }
+                }
+            }
+        }
+        for &id in state.client_set.workspace("reikai").unwrap().clients() {
+            if r.locate(id).is_none() && !lines.iter().any(|(_, l)| *l == id) {
+                lines.push(("reikai".to_string(), id));
+            }
+        }
+    }
+    let options = lines.iter()
+        .map(|(place, id)| format!("{place:<8} {:<16} {}", window_class(x, *id), x.window_title(*id).unwrap_or_default()))
+        .collect::<Vec<_>>();
+
+    let dmenu = DMenu::new(
+        &DMenuConfig {
+            kind: DMenuKind::Suckless,
+            ignore_case: true,
+            n_lines: 20,
+            ..Default::default()
+        },
+        state.client_set.current_screen().index(),
+    );
+    let choice = match dmenu.build_menu(options) {
+        Ok(MenuMatch::Line(ix, _)) => lines[ix].1,
+        _ => return Ok(()),
+    };
+
+    // in a parked activity: switch to it, then it is in a ring like any other
+    let activity = rings.borrow().activities.iter()
+        .find(|(_, a)| a.tags.iter().flatten().any(|ring| ring.ring.contains(&choice)))
+        .map(|(name, _)| name.clone());
+    if let Some(name) = activity {
+        enter_activity(state, x, &name)?;
+    }
+
+    let cs = &mut state.client_set;
+    let located = rings.borrow().locate(choice);
+    let sticky = rings.borrow_mut().sticky.values_mut()
+        .find_map(|(_, ring)| ring.ring.iter().position(|&e| e == choice).map(|p| ring.focus = p))
+        .is_some();
+    match located {
+        _ if sticky => { },
+        Some((i, c, p)) => {
+            rings.borrow_mut().tags[i][c].focus = p;
+            let tag = rings.borrow().tag_names[i].clone();
+            cs.focus_tag(&tag);
+        },
+        None => {
+            let wstag = cs.current_workspace().tag().to_string();
+            let fc = cs.current_client().copied();
+            if !rings.borrow_mut().insert(choice, fc, &wstag) {
+                // parked behind pinned windows, it is in a ring now but can't be shown
+                rebuild(rings, cs);
+                return x.refresh(state);
+            }
+        },
+    }
+    rings.borrow_mut().set_focus(choice);
+    rebuild(rings, cs);
+    cs.focus_client(&choice);
+    x.refresh(state)
+}
+
+fn store_view<X: XConn>(state: &mut State<X>, name: &str) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    let cs = &state.client_set;
+    rings.borrow_mut().save_view(name, cs.current_workspace().tag(), cs.current_client().copied());
+    Ok(())
+}
+
+// returns false if there is no view with that name
+fn restore_view<X: XConn>(state: &mut State<X>, x: &X, name: &str) -> Result<bool> {
+    let rings = state.extension::<Rings>()?;
+    let view = match rings.borrow().views.get(name).cloned() {
+        Some(view) => view,
+        None => return Ok(false),
+    };
+    rings.borrow_mut().apply_view(&view);
+    let tag = rings.borrow().tag_names[view.tag].clone();
+    let cs = &mut state.client_set;
+    cs.focus_tag(&tag);
+    let focus = view.focus
+        .filter(|id| rings.borrow().is_focused_in_a_ring(*id))
+        .or_else(|| rings.borrow().focuses(view.tag).first().copied());
+    if let Some(fid) = focus {
+        rings.borrow_mut().set_focus(fid);
+    }
+    rebuild(rings.clone(), cs);
+    if let Some(fid) = focus {
+        cs.focus_client(&fid);
+    }
+    x.refresh(state)?;
+    // fullscreen is ewmh state of the window itself, toggle it where it differs
+    if let Some(fid) = focus {
+        let is_fullscreen = rings.borrow().fullscreen.contains(&fid);
+        if (view.fullscreen == Some(fid)) != is_fullscreen {
+            toggle_fullscreen().call(state, x)?;
+        }
+    }
+    Ok(true)
+}
+
+// pick one of names or type a new one
+fn name_menu<X: XConn>(state: &State<X>, names: Vec<String>) -> Option<String> {
+    let dmenu = DMenu::new(
+        &DMenuConfig {
+            kind: DMenuKind::Suckless,
+            ..Default::default()
+        },
+        state.client_set.current_screen().index(),
+    );
+    match dmenu.build_menu(names) {
+        Ok(MenuMatch::Line(_, name)) | Ok(MenuMatch::UserInput(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn save_view<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, _: &X| {
+        let rings = state.extension::<Rings>()?;
+        let mut names = rings.borrow().views.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        match name_menu(state, names) {
+            Some(name) => store_view(state, &name),
+            None => Ok(()),
+        }
+    })
+}
+
+fn load_view<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let mut names = rings.borrow().views.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        if let Some(name) = name_menu(state, names) {
+            restore_view(state, x, &name)?;
+        }
+        Ok(())
+    })
+}
+
+fn activity_names(rings: &Rings) -> Vec<String> {
+    let mut names = rings.activities.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn enter_activity<X: XConn>(state: &mut State<X>, x: &X, name: &str) -> Result<()> {
+    let rings = state.extension::<Rings>()?;
+    if !rings.borrow_mut().switch_activity(name) { return Ok(()); }
+    let cs = &mut state.client_set;
+    rebuild(rings.clone(), cs);
+    let index = rings.borrow().tag_indices.get(cs.current_workspace().tag()).copied();
+    let fid = index.and_then(|i| rings.borrow().focuses(i).first().copied());
+    if let Some(fid) = fid {
+        rings.borrow_mut().set_focus(fid);
+        cs.focus_client(&fid);
+    }
+    x.refresh(state)
+}
+
+fn switch_activity<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let names = activity_names(&rings.borrow());
+        if let Some(name) = name_menu(state, names) {
+            enter_activity(state, x, &name)?;
+        }
+        Ok(())
+    })
+}
+
+fn send_to_activity<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| {
+        let rings = state.extension::<Rings>()?;
+        let fid = match state.client_set.current_client().copied() { Some(fid) => fid, None => return Ok(()) };
+        let names = activity_names(&rings.borrow());
+        let name = match name_menu(state, names) { Some(name) => name, None => return Ok(()) };
+        if !rings.borrow_mut().send_to_activity(fid, &name) { return Ok(()); }
+        let cs = &mut state.client_set;
+        rebuild(rings.clone(), cs);
+        let index = rings.borrow().tag_indices.get(cs.current_workspace().tag()).copied();
+        let nfid = index.and_then(|i| rings.borrow().focuses(i).first().copied());
+        if let Some(nfid) = nfid {
+            rings.borrow_mut().set_focus(nfid);
+            cs.focus_client(&nfid);
+        }
+        x.refresh(state)
+    })
+}
+
+// returns false if there is no template with that name
+fn launch_template<X: XConn>(state: &mut State<X>, name: &str) -> Result<bool> {
+    let slots = match TEMPLATES.iter().find(|(n, _)| *n == name) {
+        Some((_, slots)) => *slots,
+        None => return Ok(false),
+    };
+    let rings = state.extension::<Rings>()?;
+    let index = match rings.borrow().tag_indices.get(state.client_set.current_workspace().tag()) {
+        Some(i) => *i,
+        None => return Ok(false),
+    };
+    for slot in slots {
+        let mut parts = slot.cmd.split_whitespace();
+        let prog = match parts.next() { Some(p) => p, None => continue };
+        // not through a shell, so the pid is the one the window will report
+        let child = Command::new(prog)
+            .args(parts)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        rings.borrow_mut().pending.push(Pending {
+            pid: child.id(),
+            class: slot.class.to_string(),
+            tag: index,
+            col: slot.col,
+            forks: slot.forks,
+            spawned: Instant::now(),
+        });
+    }
+    Ok(true)
+}
+
+fn template_menu<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, _: &X| {
+        let names = TEMPLATES.iter().map(|(n, _)| n.to_string()).collect();
+        if let Some(name) = name_menu(state, names) {
+            launch_template(state, &name)?;
+        }
+        Ok(())
+    })
+}
+
+fn window_switcher<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| switch_window(state, x))
+}
+
 // TODO
 pub fn action_menu<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|state, x: &X| {
@@ -685,6 +2200,7 @@ pub fn action_menu<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
                     }
                 },
                 "insert" => {},
+                "focus" => switch_window(state, x)?,
                 _ => { },
             }
         }
@@ -697,16 +2213,21 @@ fn log_status<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
         println!("status:");
         let rings = state.extension::<Rings>()?;
         let rings = rings.borrow();
+        println!("activity: {}", rings.activity);
         println!("in rings: ");
-        for (i, (l, r)) in rings.tags.iter().enumerate() {
+        for (i, cols) in rings.tags.iter().enumerate() {
             println!(" {}:", rings.tag_names[i]);
-            print!("  l: ");
-            for id in &l.ring {
-                print!("{}, ", id);
+            for (c, ring) in cols.iter().take(rings.cols[i]).enumerate() {
+                print!("  {c}{}: ", if ring.locked { " (locked)" } else { "" });
+                for id in &ring.ring {
+                    print!("{}, ", id);
+                }
+                println!();
             }
-            println!();
-            print!("  r: ");
-            for id in &r.ring {
+        }
+        for (si, (c, ring)) in rings.sticky.iter() {
+            print!(" sticky screen {si} column {c}{}: ", if ring.locked { " (locked)" } else { "" });
+            for id in &ring.ring {
                 print!("{}, ", id);
             }
             println!();
@@ -746,11 +2267,278 @@ fn main() -> Result<()> {
     config.compose_or_set_manage_hook(rings_manage);
     config.compose_or_set_refresh_hook(rings_refresh);
     config.compose_or_set_event_hook(rings_event);
+    config.compose_or_set_refresh_hook(screens_refresh);
+    config.compose_or_set_refresh_hook(smart_borders_refresh);
+    config.compose_or_set_startup_hook(struts_startup);
+    config.compose_or_set_event_hook(struts_event);
+    config.compose_or_set_refresh_hook(struts_refresh);
+    config.compose_or_set_event_hook(screens_event);
+    if I3_IPC {
+        config.compose_or_set_startup_hook(ipc::ipc_startup);
+        config.compose_or_set_refresh_hook(ipc::ipc_refresh);
+        config.compose_or_set_event_hook(ipc::ipc_event);
+    }
 
     let mut wm = WindowManager::new(config, key_bindings, HashMap::new(), conn)?;
     wm.state.add_extension(OgWindowSize::default());
     wm.state.add_extension(Rings::new());
+    wm.state.add_extension(ScreenMemory::default());
+    wm.state.add_extension(Struts::default());
+    wm.state.add_extension(BorderWidths::default());
+    wm.state.add_extension(RingOverlay::default());
     wm.state.client_set.add_invisible_workspace("reikai")?;
 
     wm.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(ids: &[u32], focus: usize) -> Ring {
+        Ring { ring: ids.iter().map(|i| Xid::from(*i)).collect(), focus, locked: false }
+    }
+
+    #[test]
+    fn deleting_a_hidden_member_keeps_the_shown_one() {
+        for p in [0, 2] {
+            let mut r = ring(&[1, 2, 3], 1);
+            let id = r.ring[p];
+            assert_eq!(r.delete(id), (false, Some(Xid::from(2))));
+            assert_eq!(r.focus(), Some(Xid::from(2)));
+        }
+    }
+
+    #[test]
+    fn deleting_the_shown_member_shows_the_previous_one() {
+        let mut r = ring(&[1, 2, 3], 1);
+        assert_eq!(r.delete(Xid::from(2)), (false, Some(Xid::from(1))));
+        let mut r = ring(&[1, 2, 3], 0);
+        assert_eq!(r.delete(Xid::from(1)), (false, Some(Xid::from(3))));
+        let mut r = ring(&[1], 0);
+        assert_eq!(r.delete(Xid::from(1)), (true, None));
+        assert_eq!(r.delete(Xid::from(1)), (false, None));
+    }
+
+    fn rect(x: u32, y: u32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    fn screens(list: &[(Rect, &str)]) -> Vec<(Rect, String)> {
+        list.iter().map(|(r, t)| (*r, t.to_string())).collect()
+    }
+
+    #[test]
+    fn screens_side_by_side_go_left_to_right() {
+        let order = order_screens(screens(&[
+            (rect(3840, 0, 1920, 1080), "l"),
+            (rect(0, 0, 1920, 1080), "g"),
+            (rect(1920, 0, 1920, 1080), "m"),
+        ]));
+        assert_eq!(order, ["g", "m", "l"]);
+    }
+
+    #[test]
+    fn stacked_screens_go_top_to_bottom_before_the_next_column() {
+        let order = order_screens(screens(&[
+            (rect(1920, 0, 1920, 1080), "l"),
+            (rect(0, 1080, 1920, 1080), "m"),
+            (rect(0, 0, 1920, 1080), "g"),
+        ]));
+        assert_eq!(order, ["g", "m", "l"]);
+    }
+
+    #[test]
+    fn smaller_screen_centered_above_a_wide_one_is_stacked() {
+        let order = order_screens(screens(&[
+            (rect(0, 1080, 3840, 1080), "m"),
+            (rect(960, 0, 1920, 1080), "g"),
+        ]));
+        assert_eq!(order, ["g", "m"]);
+    }
+
+    fn strut(list: &[[u32; 12]]) -> HashMap<Xid, [u32; 12]> {
+        list.iter().enumerate().map(|(i, s)| (Xid::from(i as u32 + 1), *s)).collect()
+    }
+
+    #[test]
+    fn partial_top_strut_only_reserves_on_its_screen() {
+        let struts = strut(&[[0, 0, 24, 0, 0, 0, 0, 0, 1920, 3839, 0, 0]]);
+        let root = (3840, 1080);
+        assert_eq!(reserved_for(rect(0, 0, 1920, 1080), root, &struts), SetReserved::default());
+        assert_eq!(
+            reserved_for(rect(1920, 0, 1920, 1080), root, &struts),
+            SetReserved { top: 24, ..Default::default() },
+        );
+    }
+
+    #[test]
+    fn bottom_strut_reserves_on_the_lower_stacked_screen() {
+        let struts = strut(&[[0, 0, 0, 30, 0, 0, 0, 0, 0, 0, 0, 1919]]);
+        let root = (1920, 2160);
+        assert_eq!(reserved_for(rect(0, 0, 1920, 1080), root, &struts), SetReserved::default());
+        assert_eq!(
+            reserved_for(rect(0, 1080, 1920, 1080), root, &struts),
+            SetReserved { bottom: 30, ..Default::default() },
+        );
+    }
+
+    #[test]
+    fn side_struts_are_measured_from_the_root_edges() {
+        let struts = strut(&[
+            [0, 50, 0, 0, 0, 0, 0, 1079, 0, 0, 0, 0],
+            [40, 0, 0, 0, 0, 1079, 0, 0, 0, 0, 0, 0],
+        ]);
+        let root = (3840, 1080);
+        assert_eq!(
+            reserved_for(rect(0, 0, 1920, 1080), root, &struts),
+            SetReserved { left: 40, ..Default::default() },
+        );
+        assert_eq!(
+            reserved_for(rect(1920, 0, 1920, 1080), root, &struts),
+            SetReserved { right: 50, ..Default::default() },
+        );
+    }
+
+    #[test]
+    fn legacy_strut_spans_every_screen_on_that_edge() {
+        let m = u32::MAX;
+        let struts = strut(&[[0, 0, 24, 0, 0, m, 0, m, 0, m, 0, m]]);
+        let root = (3840, 1080);
+        for s in [rect(0, 0, 1920, 1080), rect(1920, 0, 1920, 1080)] {
+            assert_eq!(reserved_for(s, root, &struts), SetReserved { top: 24, ..Default::default() });
+        }
+    }
+
+    fn xid(n: u32) -> Xid {
+        Xid::from(n)
+    }
+
+    fn shown(rings: &Rings, index: usize) -> Vec<Option<Xid>> {
+        rings.visible(index).iter().map(|r| r.focus()).collect()
+    }
+
+    // tag g with one window per column: 1, 2, ...
+    fn filled(n: usize) -> Rings {
+        let mut rings = Rings::new();
+        rings.set_columns(0, n);
+        for i in 1..=n as u32 {
+            assert!(rings.insert(xid(i), None, "g"));
+        }
+        rings
+    }
+
+    #[test]
+    fn new_window_skips_a_pinned_column() {
+        let mut rings = filled(2);
+        rings.pinned.insert(xid(1));
+        assert!(rings.insert(xid(3), Some(xid(1)), "g"));
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(3))]);
+    }
+
+    #[test]
+    fn new_window_is_parked_when_every_column_is_pinned() {
+        let mut rings = filled(2);
+        rings.pinned.extend([xid(1), xid(2)]);
+        assert!(!rings.insert(xid(3), Some(xid(2)), "g"));
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(2))]);
+        assert_eq!(rings.tags[0][1].members(), [xid(2), xid(3)]);
+    }
+
+    #[test]
+    fn new_window_is_parked_when_every_column_is_locked() {
+        let mut rings = filled(2);
+        rings.tags[0][0].locked = true;
+        rings.tags[0][1].locked = true;
+        assert!(!rings.insert(xid(3), Some(xid(1)), "g"));
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(2))]);
+        assert_eq!(rings.tags[0][0].members(), [xid(1), xid(3)]);
+    }
+
+    #[test]
+    fn new_window_wraps_around_to_the_first_free_column() {
+        let mut rings = filled(3);
+        rings.pinned.insert(xid(3));
+        rings.tags[0][0].locked = true;
+        assert!(rings.insert(xid(4), Some(xid(3)), "g"));
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(4)), Some(xid(3))]);
+    }
+
+    #[test]
+    fn swapping_skips_a_locked_neighbour() {
+        let mut rings = filled(3);
+        rings.tags[0][1].locked = true;
+        assert!(rings.swap_cols(Some(xid(1)), "g", true));
+        assert_eq!(shown(&rings, 0), [Some(xid(3)), Some(xid(2)), Some(xid(1))]);
+        assert_eq!(rings.active_col[0], 2);
+    }
+
+    #[test]
+    fn swapping_left_wraps_around() {
+        let mut rings = filled(3);
+        assert!(rings.swap_cols(Some(xid(1)), "g", false));
+        assert_eq!(shown(&rings, 0), [Some(xid(3)), Some(xid(2)), Some(xid(1))]);
+    }
+
+    #[test]
+    fn no_swap_when_the_other_columns_are_locked() {
+        let mut rings = filled(3);
+        rings.tags[0][1].locked = true;
+        rings.tags[0][2].locked = true;
+        assert!(!rings.swap_cols(Some(xid(1)), "g", true));
+        assert!(!rings.swap_cols(Some(xid(2)), "g", false));
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(2)), Some(xid(3))]);
+    }
+
+    // what rebuild does for a tag shown on screen 0
+    fn show(rings: &mut Rings, index: usize) {
+        rings.screen_of = Default::default();
+        rings.screen_of[index] = Some(0);
+        let n = rings.wanted_columns(0);
+        rings.set_columns(index, n);
+    }
+
+    fn all_members(rings: &Rings) -> Vec<Xid> {
+        let mut res = rings.tags.iter().flatten()
+            .chain(rings.sticky.values().map(|(_, r)| r))
+            .flat_map(|r| r.ring.clone())
+            .collect::<Vec<_>>();
+        res.sort_by_key(|id| **id);
+        res
+    }
+
+    #[test]
+    fn shrinking_and_growing_splits_merged_rings_back_out() {
+        let mut rings = filled(3);
+        rings.set_columns(0, 1);
+        assert_eq!(shown(&rings, 0), [Some(xid(1))]);
+        assert_eq!(rings.tags[0][0].members(), [xid(1), xid(2), xid(3)]);
+        rings.set_columns(0, 3);
+        assert_eq!(shown(&rings, 0), [Some(xid(1)), Some(xid(2)), Some(xid(3))]);
+    }
+
+    #[test]
+    fn unsticking_on_another_tag_keeps_every_window() {
+        for sticky_col in [0, 1] {
+            let mut rings = Rings::new();
+            rings.tags[0][sticky_col].ring = vec![xid(1), xid(2)];
+            rings.tags[0][1 - sticky_col].ring = vec![xid(9)];
+            rings.tags[1][0].ring = vec![xid(5)];
+            rings.tags[1][1].ring = vec![xid(6)];
+            let before = all_members(&rings);
+
+            show(&mut rings, 0);
+            assert!(rings.toggle_sticky(xid(1), "g", 0));
+            show(&mut rings, 0);
+            show(&mut rings, 1);
+            assert!(!rings.toggle_sticky(xid(1), "m", 0));
+            show(&mut rings, 1);
+            assert_eq!(all_members(&rings), before);
+            assert_eq!(rings.visible(1).iter().map(|r| r.len()).sum::<usize>(), 4);
+
+            show(&mut rings, 0);
+            assert_eq!(all_members(&rings), before);
+            assert_eq!(shown(&rings, 0), [Some(xid(9)), None]);
+        }
+    }
+}
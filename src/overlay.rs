@@ -0,0 +1,108 @@
+// short lived list of the members of a ring, shown after rotating or swapping in it
+
+use penrose::{
+    pure::geometry::Rect,
+    x::{ Atom, WinType },
+    Color, Xid,
+};
+use penrose_ui::Draw;
+
+use std::sync::mpsc::{ channel, Receiver, RecvTimeoutError, SendError, Sender };
+use std::time::{ Duration, Instant };
+
+const FONT: &str = "monospace";
+const POINT_SIZE: u8 = 12;
+const WIDTH: u32 = 600;
+const LINE_HEIGHT: u32 = 24;
+const PADDING: u32 = 8;
+const BG: u32 = 0x1A1B26FF;
+const FG: u32 = 0xA9B1D6FF;
+const FG_FOCUSED: u32 = 0xF7768EFF;
+
+#[derive(Debug)]
+struct Request {
+    titles: Vec<String>,
+    focus: usize,
+    screen: Rect,
+    timeout: Duration,
+}
+
+// all drawing happens on one thread with its own connection, so the wm never waits on it
+// and xlib is only ever used from that thread. a new request replaces what is on screen
+#[derive(Debug, Default)]
+pub struct RingOverlay {
+    requests: Option<Sender<Request>>,
+}
+
+impl RingOverlay {
+    pub fn show(&mut self, titles: Vec<String>, focus: usize, screen: Rect, timeout: Duration) {
+        if titles.is_empty() { return; }
+        let req = Request { titles, focus, screen, timeout };
+        let req = match &self.requests {
+            Some(tx) => match tx.send(req) {
+                Ok(()) => return,
+                Err(SendError(req)) => req,
+            },
+            None => req,
+        };
+
+        // started on first use, and again if drawing failed and the thread went away
+        let (tx, rx) = channel();
+        let _ = tx.send(req);
+        std::thread::spawn(move || {
+            let _ = run(rx);
+        });
+        self.requests = Some(tx);
+    }
+}
+
+fn run(requests: Receiver<Request>) -> penrose_ui::Result<()> {
+    let mut drw = Draw::new(FONT, POINT_SIZE, Color::new_from_hex(BG))?;
+    let mut shown: Option<(Xid, Rect, Instant)> = None;
+
+    loop {
+        let req = match shown {
+            Some((id, _, end)) => match requests.recv_timeout(end.saturating_duration_since(Instant::now())) {
+                Ok(req) => req,
+                Err(RecvTimeoutError::Timeout) => {
+                    drw.destroy_window_and_surface(id)?;
+                    shown = None;
+                    continue;
+                },
+                Err(RecvTimeoutError::Disconnected) => return drw.destroy_window_and_surface(id),
+            },
+            None => match requests.recv() {
+                Ok(req) => req,
+                Err(_) => return Ok(()),
+            },
+        };
+
+        let h = LINE_HEIGHT * req.titles.len() as u32 + 2 * PADDING;
+        let r = Rect { x: 0, y: 0, w: WIDTH.min(req.screen.w), h: h.min(req.screen.h) };
+        let r = r.centered_in(&req.screen).unwrap_or(r);
+
+        // the same window is drawn over while it fits, it is only replaced when the size or place changes
+        let id = match shown {
+            Some((id, old, _)) if old == r => id,
+            Some((id, _, _)) => {
+                drw.destroy_window_and_surface(id)?;
+                drw.new_window(WinType::InputOutput(Atom::NetWindowTypeNotification), r, false)?
+            },
+            None => drw.new_window(WinType::InputOutput(Atom::NetWindowTypeNotification), r, false)?,
+        };
+        shown = Some((id, r, Instant::now() + req.timeout));
+        paint(&mut drw, id, r, &req.titles, req.focus)?;
+    }
+}
+
+fn paint(drw: &mut Draw, id: Xid, r: Rect, titles: &[String], focus: usize) -> penrose_ui::Result<()> {
+    let mut ctx = drw.context_for(id)?;
+    ctx.fill_rect(Rect { x: 0, y: 0, w: r.w, h: r.h }, Color::new_from_hex(BG))?;
+    for (i, title) in titles.iter().enumerate() {
+        let fg = if i == focus { FG_FOCUSED } else { FG };
+        ctx.reset_offset();
+        ctx.translate(0, (PADDING + i as u32 * LINE_HEIGHT) as i32);
+        ctx.draw_text(title, 0, (PADDING, PADDING), Color::new_from_hex(fg))?;
+    }
+    drw.flush(id)
+}